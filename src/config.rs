@@ -1,14 +1,69 @@
-use crate::drivers::KeyMapping;
-use clap::{value_t, App, Arg};
-use std::ffi::OsString;
+use crate::cpu::TIMER_FREQ;
+use crate::database::{self, Platform, RomInfo};
+use crate::drivers::Glyphs;
+use crate::hotkeys::HotkeyMap;
+use crate::keymap::{KeyMap, KeyMapping};
+use crate::palette::{Palette, Rgb, Theme};
+use crate::quirks::{KeyPriority, KeyWait, Quirks};
+use crate::recorder::Format;
+use crate::settings::Settings;
+use crate::synth::{Tone, Waveform};
+use clap::{arg_enum, value_t, App, Arg, ArgMatches};
+use std::ffi::{OsStr, OsString};
+use std::io::{stderr, Write};
+use std::path::{Path, PathBuf};
+
+arg_enum! {
+    #[derive(Clone, Copy, PartialEq)]
+    pub enum Frontend {
+        SDL,
+        Terminal,
+        Null,
+    }
+}
+
+// Default instructions executed per second
+pub const DEFAULT_RATE: u64 = 700;
 
 pub struct Config {
     pub rom_file: OsString,
     pub rate: Option<u64>,
-    pub key_map: KeyMapping,
+    pub key_map: KeyMap,
+    pub hotkeys: HotkeyMap,
+    pub pad_map: KeyMap,
+    pub stick_threshold: f32,
+    pub fast_forward: u32,
+    pub slow_motion: u32,
+    pub palette: Palette,
+    pub persistence: u32,
+    pub scale: u32,
+    pub integer_scaling: bool,
+    pub osd_stats: bool,
+    pub debugger: bool,
+    pub virtual_keypad: bool,
+    pub screenshot_at: Option<u64>,
+    pub screenshot_file: Option<OsString>,
+    pub record: Option<(OsString, Format)>,
+    pub record_audio: bool,
+    pub record_wav: Option<OsString>,
+    pub tone: Tone,
+    pub no_audio: bool,
+    pub audio_dump: Option<OsString>,
+    pub frontend: Frontend,
+    pub glyphs: Glyphs,
+    pub quirks: Quirks,
+    pub frames: Option<u64>,
+    pub seed: Option<u64>,
+    pub movie_record: Option<OsString>,
+    pub movie_play: Option<OsString>,
 }
 
 pub fn get_config() -> Config {
+    let default_rate = DEFAULT_RATE.to_string();
+    let rate_help = format!(
+        "Instructions executed per second. '0' for no limit (timers assume {})",
+        DEFAULT_RATE
+    );
     let matches = App::new(env!("CARGO_PKG_NAME"))
         .version(env!("CARGO_PKG_VERSION"))
         .author(env!("CARGO_PKG_AUTHORS"))
@@ -19,13 +74,32 @@ pub fn get_config() -> Config {
                 .required(true)
                 .help("Path to ROM file"),
         )
+        .arg(
+            Arg::with_name("config")
+                .short("c")
+                .long("config")
+                .value_name("FILE")
+                .help("Settings file with defaults for all or individual ROMs, config.toml in the user config directory by default"),
+        )
+        .arg(
+            Arg::with_name("rom_db")
+                .long("rom-db")
+                .value_name("DIR")
+                .help("Directory with programs.json and sha1-hashes.json from the CHIP-8 database, used to pick the platform, quirks, speed, colors and keys of known ROMs. Defaults to 'database' in the user config directory if it exists, otherwise the built-in copy"),
+        )
+        .arg(
+            Arg::with_name("platform")
+                .long("platform")
+                .value_name("ID")
+                .help("Emulate the quirks and speed of a CHIP-8 variant, e.g. originalChip8, modernChip8, chip48 or superchip"),
+        )
         .arg(
             Arg::with_name("rate")
                 .short("r")
                 .long("rate")
                 .value_name("NUM")
-                .default_value("700")
-                .help("Instructions executed per second. '0' for no limit"),
+                .default_value(&default_rate)
+                .help(&rate_help),
         )
         .arg(
             Arg::with_name("key_map")
@@ -36,18 +110,595 @@ pub fn get_config() -> Config {
                 .default_value("QWERTY")
                 .help("Keyboard mapping"),
         )
+        .arg(
+            Arg::with_name("key_map_file")
+                .long("key-map-file")
+                .value_name("FILE")
+                .help("Load the keyboard mapping from a file, one '<CHIP-8 key> = <key name>' per line. Overrides --key-map"),
+        )
+        .arg(
+            Arg::with_name("hotkeys")
+                .long("hotkeys")
+                .value_name("FILE")
+                .help("Rebind emulator controls from a file, one '<action> = <key name>' per line, e.g. 'save-state = F1'"),
+        )
+        .arg(
+            Arg::with_name("pad_map")
+                .long("pad-map")
+                .value_name("FILE")
+                .help("Load the game controller mapping from a file, one '<CHIP-8 key> = <button>' per line. Defaults to <ROM>.padmap if it exists"),
+        )
+        .arg(
+            Arg::with_name("stick_threshold")
+                .long("stick-threshold")
+                .value_name("PERCENT")
+                .default_value("50")
+                .help("How far analog sticks must be pushed to press a direction"),
+        )
+        .arg(
+            Arg::with_name("fast_forward")
+                .long("fast-forward")
+                .value_name("NUM")
+                .default_value("4")
+                .help("Speed multiplier while fast-forward (Tab) is held"),
+        )
+        .arg(
+            Arg::with_name("slow_motion")
+                .long("slow-motion")
+                .value_name("NUM")
+                .default_value("4")
+                .help("Speed divisor while slow motion (`) is toggled on"),
+        )
+        .arg(
+            Arg::with_name("theme")
+                .short("t")
+                .long("theme")
+                .possible_values(&Theme::variants())
+                .case_insensitive(true)
+                .default_value("Default")
+                .help("Display color theme"),
+        )
+        .arg(
+            Arg::with_name("fg")
+                .long("fg")
+                .value_name("RRGGBB")
+                .help("Foreground color, overrides the theme"),
+        )
+        .arg(
+            Arg::with_name("bg")
+                .long("bg")
+                .value_name("RRGGBB")
+                .help("Background color, overrides the theme"),
+        )
+        .arg(
+            Arg::with_name("persistence")
+                .long("persistence")
+                .value_name("FRAMES")
+                .default_value("0")
+                .help("Frames for pixels to fade out to reduce flicker. '0' to disable"),
+        )
+        .arg(
+            Arg::with_name("scale")
+                .short("s")
+                .long("scale")
+                .value_name("NUM")
+                .default_value("20")
+                .help("Window and screenshot size as a multiple of the display resolution"),
+        )
+        .arg(
+            Arg::with_name("integer_scaling")
+                .long("integer-scaling")
+                .help("Only scale the display by whole multiples when resizing"),
+        )
+        .arg(
+            Arg::with_name("osd_stats")
+                .long("osd-stats")
+                .help("Show performance stats on screen at startup (toggle with F3)"),
+        )
+        .arg(
+            Arg::with_name("debugger")
+                .long("debugger")
+                .help("Open the debugger window at startup (toggle with F8)"),
+        )
+        .arg(
+            Arg::with_name("virtual_keypad")
+                .long("virtual-keypad")
+                .help("Show a keypad under the display that can be clicked or touched"),
+        )
+        .arg(
+            Arg::with_name("screenshot_at")
+                .long("screenshot-at")
+                .value_name("FRAME")
+                .conflicts_with("frames")
+                .help("Run headless until FRAME, save a screenshot and exit"),
+        )
+        .arg(
+            Arg::with_name("screenshot_file")
+                .long("screenshot-file")
+                .value_name("FILE")
+                .requires("screenshot_at")
+                .help("PNG file written by --screenshot-at"),
+        )
+        .arg(
+            Arg::with_name("record")
+                .long("record")
+                .value_name("FILE")
+                .conflicts_with("record_raw")
+                .help("Record gameplay to an animated GIF from startup"),
+        )
+        .arg(
+            Arg::with_name("record_raw")
+                .long("record-raw")
+                .value_name("FILE")
+                .help("Record unscaled RGB24 frames at 60fps from startup. '-' for stdout"),
+        )
+        .arg(
+            Arg::with_name("record_audio")
+                .long("record-audio")
+                .help("Also record audio to a WAV file next to recordings"),
+        )
+        .arg(
+            Arg::with_name("record_wav")
+                .long("record-wav")
+                .value_name("FILE")
+                .help("Record the buzzer to a WAV file from startup, also when headless"),
+        )
+        .arg(
+            Arg::with_name("beep_freq")
+                .long("beep-freq")
+                .value_name("HZ")
+                .default_value("240")
+                .help("Pitch of the buzzer"),
+        )
+        .arg(
+            Arg::with_name("waveform")
+                .long("waveform")
+                .possible_values(&Waveform::variants())
+                .case_insensitive(true)
+                .default_value("Square")
+                .help("Waveform of the buzzer (cycle with F4)"),
+        )
+        .arg(
+            Arg::with_name("volume")
+                .long("volume")
+                .value_name("PERCENT")
+                .default_value("50")
+                .help("Buzzer volume from 0 to 100 (adjust with - and =)"),
+        )
+        .arg(
+            Arg::with_name("mute")
+                .long("mute")
+                .help("Start with the buzzer muted (toggle with M)"),
+        )
+        .arg(
+            Arg::with_name("no_audio")
+                .long("no-audio")
+                .help("Don't open an audio device"),
+        )
+        .arg(
+            Arg::with_name("audio_dump")
+                .long("audio-dump")
+                .value_name("FILE")
+                .conflicts_with("no_audio")
+                .help("Write audio as raw 32 bit float mono samples at 44.1kHz instead of playing it. '-' for stdout"),
+        )
+        .arg(
+            Arg::with_name("frontend")
+                .short("f")
+                .long("frontend")
+                .possible_values(&Frontend::variants())
+                .case_insensitive(true)
+                .default_value("SDL")
+                .help("Window, terminal or headless (null) user interface"),
+        )
+        .arg(
+            Arg::with_name("glyphs")
+                .long("glyphs")
+                .possible_values(&Glyphs::variants())
+                .case_insensitive(true)
+                .default_value("HalfBlock")
+                .help("Characters used to draw pixels in the terminal frontend"),
+        )
+        .arg(
+            Arg::with_name("key_wait")
+                .long("key-wait")
+                .possible_values(&KeyWait::variants())
+                .case_insensitive(true)
+                .default_value("Release")
+                .help("When FX0A (wait for key) completes: while a key is held, on press, or on release like the COSMAC VIP"),
+        )
+        .arg(
+            Arg::with_name("key_priority")
+                .long("key-priority")
+                .possible_values(&KeyPriority::variants())
+                .case_insensitive(true)
+                .default_value("First")
+                .help("Key FX0A stores when several are pressed while waiting"),
+        )
+        .arg(
+            Arg::with_name("frames")
+                .long("frames")
+                .value_name("NUM")
+                .help("Exit after NUM frames (60 per second) of emulated time"),
+        )
+        .arg(
+            Arg::with_name("seed")
+                .long("seed")
+                .value_name("NUM")
+                .help("Seed for random numbers, random by default"),
+        )
+        .arg(
+            Arg::with_name("movie_record")
+                .long("movie-record")
+                .value_name("FILE")
+                .conflicts_with("movie_play")
+                .help("Record keypad input to a movie file for exact replay"),
+        )
+        .arg(
+            Arg::with_name("movie_play")
+                .long("movie-play")
+                .value_name("FILE")
+                .help("Replay keypad input from a movie file, headless runs stop at its end"),
+        )
         .get_matches();
 
     let rom_file = matches.value_of_os("rom").unwrap().to_owned();
-    let rate = match value_t!(matches.value_of("rate"), u64).unwrap_or_else(|e| e.exit()) {
+    let rom_hash = std::fs::read(&rom_file)
+        .ok()
+        .map(|rom| sha1_smol::Sha1::from(rom).digest().to_string());
+
+    // Options not given on the command line come from the settings file
+    let settings_file = match matches.value_of_os("config") {
+        Some(file) => Some(Path::new(file).to_owned()),
+        None => Settings::default_path().filter(|path| path.is_file()),
+    };
+    let settings = match settings_file {
+        Some(file) => Settings::load(&file, Path::new(&rom_file), rom_hash.as_deref())
+            .unwrap_or_else(|e| {
+                writeln!(&mut stderr(), "{:?}: {}", file, e).ok();
+                std::process::exit(1);
+            }),
+        None => Settings::default(),
+    };
+
+    // and then from the ROM database
+    let rom_db = match matches.value_of_os("rom_db") {
+        Some(dir) => Some(PathBuf::from(dir)),
+        None => settings
+            .get("rom-db")
+            .map(PathBuf::from)
+            .or_else(|| database::default_dir().filter(|dir| dir.is_dir())),
+    };
+    let rom_info = rom_hash.as_deref().and_then(|hash| match &rom_db {
+        Some(dir) => RomInfo::lookup(dir, hash).unwrap_or_else(|e| {
+            writeln!(&mut stderr(), "ROM database: {}, continuing without it", e).ok();
+            None
+        }),
+        None => RomInfo::find(hash),
+    });
+    let mut known = Settings::default();
+    if let Some(info) = &rom_info {
+        if let Some(platform) = &info.platform {
+            known.insert("platform", platform.clone());
+        }
+        if let Some(tickrate) = info.tickrate {
+            known.insert("rate", (tickrate * TIMER_FREQ).to_string());
+        }
+        if let Some((background, foreground)) = &info.colors {
+            known.insert("bg", background.clone());
+            known.insert("fg", foreground.clone());
+        }
+    }
+
+    let none = Settings::default();
+    let options = Options {
+        matches: &matches,
+        settings: &settings,
+        database: &known,
+    };
+    let file_options = Options {
+        matches: &matches,
+        settings: &settings,
+        database: &none,
+    };
+    let command_line = Options {
+        matches: &matches,
+        settings: &none,
+        database: &none,
+    };
+
+    let platform = options.value_of("platform").map(|id| {
+        Platform::find(id).unwrap_or_else(|| {
+            writeln!(
+                &mut stderr(),
+                "Unknown platform {:?}, expected one of {}",
+                id,
+                Platform::ids().join(", ")
+            )
+            .ok();
+            std::process::exit(1);
+        })
+    });
+    if let Some(info) = &rom_info {
+        let mut about = info.title.clone();
+        if !info.authors.is_empty() {
+            about += &format!(" by {}", info.authors.join(", "));
+        }
+        if let Some(platform) = &platform {
+            about += &format!(" ({})", platform.name);
+        }
+        writeln!(&mut stderr(), "{}", about).ok();
+    }
+    if let Some(platform) = &platform {
+        if platform.resolutions.iter().any(|r| r != "64x32") {
+            writeln!(
+                &mut stderr(),
+                "{} programs may use display modes other than 64x32, which are not emulated",
+                platform.name
+            )
+            .ok();
+        }
+    }
+
+    // Platforms run at their own speed unless told otherwise
+    let platform_rate = platform
+        .as_ref()
+        .and_then(|platform| platform.tickrate)
+        .map(|tickrate| tickrate * TIMER_FREQ);
+    let rate = match (options.is_set("rate"), platform_rate) {
+        (false, Some(rate)) => rate,
+        _ => value_t!(options, "rate", u64).unwrap_or_else(|e| e.exit()),
+    };
+    let rate = match rate {
         0 => None,
         i => Some(i),
     };
-    let key_map = value_t!(matches, "key_map", KeyMapping).unwrap_or_else(|e| e.exit());
+    // A key map given on the command line replaces a key map file from the
+    // settings, keys from the database only apply to built-in maps
+    let key_map_options = match matches.occurrences_of("key_map") {
+        0 => &options,
+        _ => &command_line,
+    };
+    let mut key_map = match key_map_options.value_of_os("key_map_file") {
+        Some(file) => KeyMap::load(Path::new(file)).unwrap_or_else(|e| {
+            writeln!(&mut stderr(), "{:?}: {}", file, e).ok();
+            std::process::exit(1);
+        }),
+        None => {
+            KeyMap::builtin(value_t!(options, "key_map", KeyMapping).unwrap_or_else(|e| e.exit()))
+        }
+    };
+    if !options.is_set("key_map") && !options.is_set("key_map_file") {
+        for (host, key) in rom_info.iter().flat_map(RomInfo::key_bindings) {
+            key_map.bind(host, key);
+        }
+    }
+    let hotkeys = match options.value_of_os("hotkeys") {
+        Some(file) => HotkeyMap::load(Path::new(file)).unwrap_or_else(|e| {
+            writeln!(&mut stderr(), "{:?}: {}", file, e).ok();
+            std::process::exit(1);
+        }),
+        None => HotkeyMap::builtin(),
+    };
+    for conflict in hotkeys.conflicts(&key_map) {
+        writeln!(
+            &mut stderr(),
+            "Hotkey {}, the keypad takes precedence",
+            conflict
+        )
+        .ok();
+    }
+    // Controller mappings can be kept next to each ROM
+    let rom_pad_map = Path::new(&rom_file).with_extension("padmap");
+    let pad_map = match matches.value_of_os("pad_map") {
+        Some(file) => Some(Path::new(file)),
+        None if rom_pad_map.is_file() => Some(rom_pad_map.as_path()),
+        None => None,
+    };
+    let pad_map = match pad_map {
+        Some(file) => KeyMap::load(file).unwrap_or_else(|e| {
+            writeln!(&mut stderr(), "{:?}: {}", file, e).ok();
+            std::process::exit(1);
+        }),
+        None => {
+            let mut pad_map = KeyMap::gamepad();
+            for (button, key) in rom_info.iter().flat_map(RomInfo::pad_bindings) {
+                pad_map.bind(button, key);
+            }
+            pad_map
+        }
+    };
+    let stick_threshold = value_t!(matches, "stick_threshold", u32)
+        .unwrap_or_else(|e| e.exit())
+        .min(100) as f32
+        / 100.0;
+    let fast_forward = value_t!(matches, "fast_forward", u32)
+        .unwrap_or_else(|e| e.exit())
+        .max(1);
+    let slow_motion = value_t!(matches, "slow_motion", u32)
+        .unwrap_or_else(|e| e.exit())
+        .max(1);
+    let theme = value_t!(options, "theme", Theme).unwrap_or_else(|e| e.exit());
+    let mut palette = Palette::from_theme(theme);
+    // As do colors for a theme given on the command line, and database
+    // colors for a theme from the settings
+    let colors = match (matches.occurrences_of("theme"), settings.get("theme")) {
+        (0, None) => &options,
+        (0, Some(_)) => &file_options,
+        _ => &command_line,
+    };
+    if colors.is_set("fg") {
+        palette.foreground = value_t!(colors, "fg", Rgb).unwrap_or_else(|e| e.exit());
+    }
+    if colors.is_set("bg") {
+        palette.background = value_t!(colors, "bg", Rgb).unwrap_or_else(|e| e.exit());
+    }
+    let persistence = value_t!(matches, "persistence", u32).unwrap_or_else(|e| e.exit());
+    let scale = value_t!(matches, "scale", u32)
+        .unwrap_or_else(|e| e.exit())
+        .max(1);
+    let integer_scaling = matches.is_present("integer_scaling");
+    let osd_stats = matches.is_present("osd_stats");
+    let debugger = matches.is_present("debugger");
+    let virtual_keypad = matches.is_present("virtual_keypad");
+    let screenshot_at = match matches.is_present("screenshot_at") {
+        true => Some(value_t!(matches, "screenshot_at", u64).unwrap_or_else(|e| e.exit())),
+        false => None,
+    };
+    let screenshot_file = matches.value_of_os("screenshot_file").map(|f| f.to_owned());
+    let record = if let Some(file) = matches.value_of_os("record") {
+        Some((file.to_owned(), Format::Gif))
+    } else {
+        matches
+            .value_of_os("record_raw")
+            .map(|file| (file.to_owned(), Format::Raw))
+    };
+    let record_audio = matches.is_present("record_audio");
+    let record_wav = matches.value_of_os("record_wav").map(|f| f.to_owned());
+    let beep_freq = value_t!(options, "beep_freq", f32).unwrap_or_else(|e| e.exit());
+    if !beep_freq.is_finite() || beep_freq <= 0.0 {
+        writeln!(
+            &mut stderr(),
+            "beep-freq: {} is not a frequency above 0",
+            beep_freq
+        )
+        .ok();
+        std::process::exit(1);
+    }
+    let tone = Tone {
+        frequency: beep_freq,
+        waveform: value_t!(options, "waveform", Waveform).unwrap_or_else(|e| e.exit()),
+        volume: value_t!(options, "volume", u32)
+            .unwrap_or_else(|e| e.exit())
+            .min(100) as f32
+            / 100.0,
+        muted: options.is_present("mute"),
+    };
+    // An audio dump asked for on the command line wins over the settings
+    let audio_dump = matches.value_of_os("audio_dump").map(|f| f.to_owned());
+    let no_audio = options.is_present("no_audio") && audio_dump.is_none();
+    let glyphs = value_t!(matches, "glyphs", Glyphs).unwrap_or_else(|e| e.exit());
+    let mut quirks = Quirks {
+        key_wait: value_t!(options, "key_wait", KeyWait).unwrap_or_else(|e| e.exit()),
+        key_priority: value_t!(options, "key_priority", KeyPriority).unwrap_or_else(|e| e.exit()),
+        ..Quirks::default()
+    };
+    if let Some(platform) = &platform {
+        platform.apply(&mut quirks);
+        if let Some(info) = &rom_info {
+            info.apply_quirks(&platform.id, &mut quirks);
+        }
+    }
+
+    // Screenshots at a given frame are taken headless
+    let (frontend, frames) = match screenshot_at {
+        Some(frame) => (Frontend::Null, Some(frame)),
+        None => (
+            value_t!(matches, "frontend", Frontend).unwrap_or_else(|e| e.exit()),
+            match matches.is_present("frames") {
+                true => Some(value_t!(matches, "frames", u64).unwrap_or_else(|e| e.exit())),
+                false => None,
+            },
+        ),
+    };
+
+    // Only one output can go to stdout
+    let to_stdout = |name| matches.value_of_os(name) == Some(OsStr::new("-"));
+    let stdout_users: Vec<&str> = [
+        ("--audio-dump -", to_stdout("audio_dump")),
+        ("--record-raw -", to_stdout("record_raw")),
+        ("--frontend Terminal", frontend == Frontend::Terminal),
+    ]
+    .iter()
+    .filter(|(_, used)| *used)
+    .map(|(name, _)| *name)
+    .collect();
+    if stdout_users.len() > 1 {
+        writeln!(
+            &mut stderr(),
+            "{} cannot share stdout",
+            stdout_users.join(" and ")
+        )
+        .ok();
+        std::process::exit(1);
+    }
+
+    let seed = match matches.is_present("seed") {
+        true => Some(value_t!(matches, "seed", u64).unwrap_or_else(|e| e.exit())),
+        false => None,
+    };
+    let movie_record = matches.value_of_os("movie_record").map(|f| f.to_owned());
+    let movie_play = matches.value_of_os("movie_play").map(|f| f.to_owned());
 
     Config {
         rom_file,
         rate,
         key_map,
+        hotkeys,
+        pad_map,
+        stick_threshold,
+        fast_forward,
+        slow_motion,
+        palette,
+        persistence,
+        scale,
+        integer_scaling,
+        osd_stats,
+        debugger,
+        virtual_keypad,
+        screenshot_at,
+        screenshot_file,
+        record,
+        record_audio,
+        record_wav,
+        tone,
+        no_audio,
+        audio_dump,
+        frontend,
+        glyphs,
+        quirks,
+        frames,
+        seed,
+        movie_record,
+        movie_play,
+    }
+}
+
+/// Command line options, falling back to the settings file and then the ROM
+/// database before their defaults. Settings use the long option names.
+struct Options<'a> {
+    matches: &'a ArgMatches<'a>,
+    settings: &'a Settings,
+    database: &'a Settings,
+}
+
+impl<'a> Options<'a> {
+    fn setting(&self, name: &str) -> Option<&'a str> {
+        let key = name.replace('_', "-");
+        match self.matches.occurrences_of(name) {
+            0 => self.settings.get(&key).or_else(|| self.database.get(&key)),
+            _ => None,
+        }
+    }
+
+    /// Whether an option was given on the command line or in the settings
+    fn is_set(&self, name: &str) -> bool {
+        self.matches.occurrences_of(name) > 0 || self.setting(name).is_some()
+    }
+
+    fn value_of(&self, name: &str) -> Option<&'a str> {
+        self.setting(name).or_else(|| self.matches.value_of(name))
+    }
+
+    fn value_of_os(&self, name: &str) -> Option<&'a OsStr> {
+        self.setting(name)
+            .map(OsStr::new)
+            .or_else(|| self.matches.value_of_os(name))
+    }
+
+    /// Switches, for options with values use `is_set`
+    fn is_present(&self, name: &str) -> bool {
+        let key = name.replace('_', "-");
+        self.matches.is_present(name) || self.settings.flag(&key) || self.database.flag(&key)
     }
 }
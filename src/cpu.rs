@@ -1,3 +1,8 @@
+use crate::quirks::{KeyPriority, KeyWait, Quirks};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::time::Duration;
+
 // Display
 pub const DISPLAY_W: usize = 64;
 pub const DISPLAY_H: usize = 32;
@@ -11,6 +16,13 @@ pub const KEY_SIZE: usize = 0x10;
 
 const PROGRAM_OFFSET: usize = 0x200; // Program load address
 
+// Timers
+pub const TIMER_FREQ: u64 = 60; // Delay and sound timer frequency in Hz
+
+// FNV-1a parameters for state hashes
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0100_0000_01b3;
+
 // Fontset
 const FONTSET_OFFSET: usize = 0x50;
 const FONTSET_SIZE: usize = 0x50;
@@ -45,6 +57,14 @@ pub enum KeyState {
     NotPressed,
 }
 
+/// Change of a key since the previous cycle
+#[derive(Clone, Copy, PartialEq)]
+pub enum KeyEdge {
+    Unchanged,
+    Pressed,
+    Released,
+}
+
 #[derive(Clone, Copy, PartialEq)]
 pub enum PixelState {
     On,
@@ -54,7 +74,22 @@ pub enum PixelState {
 #[derive(Clone, Copy)]
 pub struct CycleInput {
     pub keys: [KeyState; KEY_SIZE],
-    pub decrement_timer: bool,
+    pub edges: [KeyEdge; KEY_SIZE],
+}
+
+impl CycleInput {
+    /// Input with edges relative to the keys of the previous cycle
+    pub fn new(keys: [KeyState; KEY_SIZE], prev_keys: &[KeyState; KEY_SIZE]) -> Self {
+        let mut edges = [KeyEdge::Unchanged; KEY_SIZE];
+        for (edge, (&key, &prev)) in edges.iter_mut().zip(keys.iter().zip(prev_keys.iter())) {
+            *edge = match (prev, key) {
+                (KeyState::NotPressed, KeyState::Pressed) => KeyEdge::Pressed,
+                (KeyState::Pressed, KeyState::NotPressed) => KeyEdge::Released,
+                _ => KeyEdge::Unchanged,
+            };
+        }
+        Self { keys, edges }
+    }
 }
 
 #[derive(Clone, Copy)]
@@ -62,9 +97,26 @@ pub struct CycleOutput<'a> {
     pub state: CPUState,
     pub gfx: &'a [PixelState; GFX_SIZE],
     pub beep: bool,
+    pub timer_tick: bool, // Timers were decremented, i.e. a 60Hz frame ended
+    pub polled_key: Option<usize>, // Key tested by EX9E or EXA1
+}
+
+/// Copy of the CPU registers for display
+#[allow(non_snake_case)]
+#[derive(Clone, Copy)]
+pub struct Registers {
+    pub V: [u8; REG_V_SIZE],
+    pub I: usize,
+    pub PC: usize,
+    pub SP: usize,
+    pub stack: [usize; STACK_SIZE],
+    pub delay_timer: u8,
+    pub sound_timer: u8,
 }
 
 #[allow(non_snake_case)]
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Clone)]
 pub struct CPU {
     mem: [u8; MEM_SIZE],         //Main memory
     gfx: [PixelState; GFX_SIZE], // Framebuffer
@@ -83,12 +135,30 @@ pub struct CPU {
     stack: [usize; STACK_SIZE],
     SP: usize, // Stack pointer
 
+    // emulated time
+    cycles: u64, // Instructions executed since reset
+    rate: u64,   // Emulated instructions per second
+
+    // Key tested by EX9E or EXA1 this cycle
+    polled_key: Option<usize>,
+
+    // The previous cycle ended a 60Hz frame
+    vblank: bool,
+
+    // FX0A, keys pressed since it started waiting
+    key_presses: u16,
+    first_press: Option<usize>,
+
     // state
     state: CPUState,
+    rng: StdRng, // Seeded so runs can be replayed exactly
+    quirks: Quirks,
 }
 
 impl CPU {
-    pub fn new(program: &[u8]) -> Self {
+    /// Timers are derived from `rate`, the emulated instructions per second.
+    /// Random numbers come from `seed`.
+    pub fn new(program: &[u8], rate: u64, seed: u64, quirks: Quirks) -> Self {
         let mut cpu = Self {
             mem: [0; MEM_SIZE],
             gfx: [PixelState::Off; GFX_SIZE],
@@ -100,7 +170,15 @@ impl CPU {
             sound_timer: 0,
             stack: [0; STACK_SIZE],
             SP: 0,
+            cycles: 0,
+            rate: rate.max(1),
+            polled_key: None,
+            vblank: false,
+            key_presses: 0,
+            first_press: None,
             state: CPUState::Running,
+            rng: StdRng::seed_from_u64(seed),
+            quirks,
         };
 
         // copy fontset to main memory
@@ -112,9 +190,10 @@ impl CPU {
         cpu
     }
 
-    pub fn cycle(&mut self, input: &CycleInput) -> CycleOutput {
+    pub fn cycle(&mut self, input: &CycleInput) -> CycleOutput<'_> {
         self.state = CPUState::Running;
         self.prev_PC = self.PC;
+        self.polled_key = None;
 
         // Fetch
         let instruction = u16::from(self.mem[self.PC]) << 8 | u16::from(self.mem[self.PC + 1]);
@@ -141,12 +220,15 @@ impl CPU {
             0xC => self.opcode_c(instruction),
             0xD => self.opcode_d(instruction),
             0xE => self.opcode_e(instruction, &input.keys),
-            0xF => self.opcode_f(instruction, &input.keys),
+            0xF => self.opcode_f(instruction, input),
             _ => panic!("Unknown instruction 0x{:04x}", instruction),
         };
 
-        // Update timers
-        if input.decrement_timer {
+        // Update timers, 60Hz in emulated time
+        let prev_frame = self.frame();
+        self.cycles += 1;
+        let timer_tick = self.frame() != prev_frame;
+        if timer_tick {
             if self.delay_timer > 0 {
                 self.delay_timer -= 1;
             }
@@ -154,12 +236,73 @@ impl CPU {
                 self.sound_timer -= 1;
             }
         }
+        self.vblank = timer_tick;
 
         CycleOutput {
             state: self.state,
             gfx: &self.gfx,
             beep: self.sound_timer != 0,
+            timer_tick,
+            polled_key: self.polled_key,
+        }
+    }
+
+    pub fn gfx(&self) -> &[PixelState; GFX_SIZE] {
+        &self.gfx
+    }
+
+    pub fn memory(&self) -> &[u8] {
+        &self.mem
+    }
+
+    pub fn registers(&self) -> Registers {
+        Registers {
+            V: self.V,
+            I: self.I,
+            PC: self.PC,
+            SP: self.SP,
+            stack: self.stack,
+            delay_timer: self.delay_timer,
+            sound_timer: self.sound_timer,
+        }
+    }
+
+    /// Number of 60Hz frames elapsed in emulated time
+    pub fn frame(&self) -> u64 {
+        self.cycles * TIMER_FREQ / self.rate
+    }
+
+    /// Emulated time since reset
+    pub fn elapsed(&self) -> Duration {
+        let nanos = u128::from(self.cycles) * 1_000_000_000 / u128::from(self.rate);
+        Duration::from_nanos(nanos as u64)
+    }
+
+    /// FNV-1a hash of memory, display, registers and timers, for checking
+    /// that a replay matches the recording
+    pub fn state_hash(&self) -> u64 {
+        let mut hash = FNV_OFFSET;
+        let mut add = |bytes: &[u8]| {
+            for &byte in bytes {
+                hash ^= u64::from(byte);
+                hash = hash.wrapping_mul(FNV_PRIME);
+            }
+        };
+
+        add(&self.mem);
+        let gfx: Vec<u8> = self
+            .gfx
+            .iter()
+            .map(|&p| (p == PixelState::On) as u8)
+            .collect();
+        add(&gfx);
+        add(&self.V);
+        for value in [self.I, self.PC, self.SP].iter().chain(self.stack.iter()) {
+            add(&(*value as u16).to_le_bytes());
         }
+        add(&[self.delay_timer, self.sound_timer]);
+
+        hash
     }
 
     fn opcode_0(&mut self, instruction: u16) {
@@ -248,9 +391,18 @@ impl CPU {
         match instruction & 0xF {
             // basic bitwise operations
             0x0 => self.V[x] = self.V[y],
-            0x1 => self.V[x] |= self.V[y],
-            0x2 => self.V[x] &= self.V[y],
-            0x3 => self.V[x] ^= self.V[y],
+            0x1 => {
+                self.V[x] |= self.V[y];
+                self.logic_quirk();
+            }
+            0x2 => {
+                self.V[x] &= self.V[y];
+                self.logic_quirk();
+            }
+            0x3 => {
+                self.V[x] ^= self.V[y];
+                self.logic_quirk();
+            }
 
             // add VY to VX, VF set to 1 if carry, otherwise set to 0
             0x4 => {
@@ -276,10 +428,12 @@ impl CPU {
                 }
             }
 
-            // (undocumented) stores LSB of VX in VF, then right shifts VX by 1
+            // (undocumented) stores LSB of VX in VF, then right shifts VX by 1.
+            // Originally VY is shifted into VX.
             0x6 => {
-                self.V[0xF] = self.V[x] & 0x1;
-                self.V[x] >>= 1;
+                let value = self.shift_operand(x, y);
+                self.V[0xF] = value & 0x1;
+                self.V[x] = value >> 1;
             }
 
             // (undocumented) sets VX to (VY - VX), VF set to 1 if borrow, otherwise set to 0
@@ -293,10 +447,12 @@ impl CPU {
                 }
             }
 
-            // (undocumented) stores MSB of VX in VF, then left shifts VX by 1
+            // (undocumented) stores MSB of VX in VF, then left shifts VX by 1.
+            // Originally VY is shifted into VX.
             0xE => {
-                self.V[0xF] = (self.V[x] & 0x80) >> 7;
-                self.V[x] <<= 1;
+                let value = self.shift_operand(x, y);
+                self.V[0xF] = (value & 0x80) >> 7;
+                self.V[x] = value << 1;
             }
 
             _ => panic!("Unknown instruction 0x{:04x}", instruction),
@@ -322,7 +478,12 @@ impl CPU {
     /// BNNN
     /// Jump to address NNN + V0
     fn opcode_b(&mut self, instruction: u16) {
-        self.PC = usize::from((instruction & 0x0FFF).wrapping_add(u16::from(self.V[0])));
+        // CHIP-48 and SUPER-CHIP read it as BXNN, jumping to XNN + VX
+        let offset = match self.quirks.jump {
+            true => self.V[get_X::<usize>(instruction)],
+            false => self.V[0],
+        };
+        self.PC = usize::from((instruction & 0x0FFF).wrapping_add(u16::from(offset)));
     }
 
     /// CXNN
@@ -330,7 +491,7 @@ impl CPU {
     fn opcode_c(&mut self, instruction: u16) {
         let x: usize = get_X(instruction);
         let nn = instruction as u8;
-        let rand_val: u8 = rand::random();
+        let rand_val: u8 = self.rng.gen();
         self.V[x] = rand_val & nn;
     }
 
@@ -348,15 +509,26 @@ impl CPU {
         let vx = usize::from(self.V[x]) % DISPLAY_W;
         let vy = usize::from(self.V[y]) % DISPLAY_H;
 
+        // The COSMAC VIP waits for the display interrupt before drawing
+        if self.quirks.vblank && !self.vblank {
+            self.PC -= 2;
+            return;
+        }
+
         self.V[0xF] = 0;
 
-        let row_end = std::cmp::min(n, DISPLAY_H - vy);
+        let (row_end, col_end) = match self.quirks.wrap {
+            true => (n, 8),
+            false => (
+                std::cmp::min(n, DISPLAY_H - vy),
+                std::cmp::min(8, DISPLAY_W - vx),
+            ),
+        };
         for row in 0..row_end {
             let sprite_data = self.mem[self.I + row];
-            let col_end = std::cmp::min(8, DISPLAY_W - vx);
             for col in 0..col_end {
                 let sprite_on = (1 << (7 - col)) & sprite_data != 0;
-                let gfx_index = (vy + row) * DISPLAY_W + (vx + col);
+                let gfx_index = (vy + row) % DISPLAY_H * DISPLAY_W + (vx + col) % DISPLAY_W;
                 let pix_on = self.gfx[gfx_index] == PixelState::On;
                 if sprite_on && pix_on {
                     self.V[0xF] = 0x1;
@@ -375,6 +547,7 @@ impl CPU {
     /// Input processing
     fn opcode_e(&mut self, instruction: u16, keys: &[KeyState; KEY_SIZE]) {
         let x: usize = get_X(instruction);
+        self.polled_key = Some(usize::from(self.V[x]));
         match instruction & 0x00FF {
             // Skips next instruction if the key stored in VX is pressed
             0x9E => {
@@ -396,7 +569,7 @@ impl CPU {
 
     /// FX--
     /// Misc
-    fn opcode_f(&mut self, instruction: u16, keys: &[KeyState; KEY_SIZE]) {
+    fn opcode_f(&mut self, instruction: u16, input: &CycleInput) {
         let x: usize = get_X(instruction);
         match instruction & 0x00FF {
             // Set VX to the value of the delay timer
@@ -404,20 +577,10 @@ impl CPU {
 
             // A key press is awaited, and then stored in VX.
             // (Blocking Operation. All instruction halted until next key event)
-            0x0A => {
-                if let Some(offset) = keys
-                    .iter()
-                    .enumerate()
-                    .find_map(|(offset, key)| match *key {
-                        KeyState::Pressed => Some(offset),
-                        KeyState::NotPressed => None,
-                    })
-                {
-                    self.V[x] = offset as u8;
-                } else {
-                    self.PC -= 2;
-                }
-            }
+            0x0A => match self.wait_key(input) {
+                Some(key) => self.V[x] = key as u8,
+                None => self.PC -= 2,
+            },
 
             // Set the delay timer to VX
             0x15 => self.delay_timer = self.V[x],
@@ -443,25 +606,80 @@ impl CPU {
 
             // Stores V0 to VX (including VX) in memory starting at address I
             0x55 => {
-                for (offset, reg_val) in self.V[0..=usize::from(x)].iter().enumerate() {
+                for (offset, reg_val) in self.V[0..=x].iter().enumerate() {
                     self.mem[self.I + offset] = *reg_val;
                 }
+                self.memory_quirk(x);
             }
 
             // Fills V0 to VX (including VX) with values from memory starting at
             // address I
             0x65 => {
-                for (offset, mem_val) in self.mem[self.I..=(self.I + usize::from(x))]
-                    .iter()
-                    .enumerate()
-                {
+                for (offset, mem_val) in self.mem[self.I..=(self.I + x)].iter().enumerate() {
                     self.V[offset] = *mem_val;
                 }
+                self.memory_quirk(x);
             }
 
             _ => panic!("Unknown instruction 0x{:04x}", instruction),
         }
     }
+
+    /// 8XY1/8XY2/8XY3 reset VF on the COSMAC VIP
+    fn logic_quirk(&mut self) {
+        if self.quirks.logic {
+            self.V[0xF] = 0;
+        }
+    }
+
+    /// Value 8XY6/8XYE shift
+    fn shift_operand(&self, x: usize, y: usize) -> u8 {
+        match self.quirks.shift {
+            true => self.V[x],
+            false => self.V[y],
+        }
+    }
+
+    /// I after FX55/FX65 transferred registers up to VX
+    fn memory_quirk(&mut self, x: usize) {
+        if self.quirks.memory_leave_i_unchanged {
+            return;
+        }
+        self.I += match self.quirks.memory_increment_by_x {
+            true => x,
+            false => x + 1,
+        };
+    }
+
+    /// Key FX0A completes with this cycle, if any
+    fn wait_key(&mut self, input: &CycleInput) -> Option<usize> {
+        let with_edge = |edge| (0..KEY_SIZE).filter(move |&key| input.edges[key] == edge);
+        if self.quirks.key_wait == KeyWait::Level {
+            return (0..KEY_SIZE).find(|&key| input.keys[key] == KeyState::Pressed);
+        }
+
+        for key in with_edge(KeyEdge::Pressed) {
+            self.key_presses |= 1 << key;
+            self.first_press.get_or_insert(key);
+        }
+        let candidates = match self.quirks.key_priority {
+            KeyPriority::First => self.first_press.map_or(0, |key| 1 << key),
+            KeyPriority::Lowest => self.key_presses,
+        };
+        let edge = match self.quirks.key_wait {
+            KeyWait::Release => KeyEdge::Released,
+            _ => KeyEdge::Pressed,
+        };
+        if !with_edge(edge).any(|key| candidates & (1 << key) != 0) {
+            return None;
+        }
+        // Lowest of the candidates, which is the only one for `First`
+        let key = candidates.trailing_zeros() as usize;
+
+        self.key_presses = 0;
+        self.first_press = None;
+        Some(key)
+    }
 }
 
 #[allow(non_snake_case)]
@@ -473,3 +691,218 @@ fn get_X<T: From<u16>>(instruction: u16) -> T {
 fn get_Y<T: From<u16>>(instruction: u16) -> T {
     T::from((instruction & 0x00F0) >> 4)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // FX0A into V0, then loop forever
+    const WAIT_KEY: [u8; 4] = [0xF0, 0x0A, 0x12, 0x02];
+
+    fn keys(pressed: &[usize]) -> [KeyState; KEY_SIZE] {
+        let mut keys = [KeyState::NotPressed; KEY_SIZE];
+        for &key in pressed {
+            keys[key] = KeyState::Pressed;
+        }
+        keys
+    }
+
+    /// Run a cycle for each set of held keys, the key stored once FX0A is done
+    fn wait_key(key_wait: KeyWait, key_priority: KeyPriority, steps: &[&[usize]]) -> Option<u8> {
+        let quirks = Quirks {
+            key_wait,
+            key_priority,
+            ..Quirks::default()
+        };
+        let mut cpu = CPU::new(&WAIT_KEY, 600, 0, quirks);
+        let mut prev_keys = keys(&[]);
+        for step in steps {
+            let keys = keys(step);
+            cpu.cycle(&CycleInput::new(keys, &prev_keys));
+            prev_keys = keys;
+            if cpu.registers().PC != 0x200 {
+                return Some(cpu.registers().V[0]);
+            }
+        }
+        None
+    }
+
+    #[test]
+    fn level_first() {
+        assert_eq!(wait_key(KeyWait::Level, KeyPriority::First, &[&[]]), None);
+        assert_eq!(
+            wait_key(KeyWait::Level, KeyPriority::First, &[&[], &[7]]),
+            Some(7)
+        );
+    }
+
+    #[test]
+    fn level_lowest() {
+        assert_eq!(
+            wait_key(KeyWait::Level, KeyPriority::Lowest, &[&[5, 2]]),
+            Some(2)
+        );
+    }
+
+    #[test]
+    fn press_first() {
+        let steps: &[&[usize]] = &[&[], &[9]];
+        assert_eq!(wait_key(KeyWait::Press, KeyPriority::First, steps), Some(9));
+    }
+
+    #[test]
+    fn press_lowest() {
+        let steps: &[&[usize]] = &[&[], &[9, 3]];
+        assert_eq!(
+            wait_key(KeyWait::Press, KeyPriority::Lowest, steps),
+            Some(3)
+        );
+    }
+
+    #[test]
+    fn release_first() {
+        let steps: &[&[usize]] = &[&[5], &[5, 2], &[5]];
+        assert_eq!(wait_key(KeyWait::Release, KeyPriority::First, steps), None);
+        let steps: &[&[usize]] = &[&[5], &[5, 2], &[2]];
+        assert_eq!(
+            wait_key(KeyWait::Release, KeyPriority::First, steps),
+            Some(5)
+        );
+    }
+
+    #[test]
+    fn release_lowest() {
+        let steps: &[&[usize]] = &[&[5], &[5, 2]];
+        assert_eq!(wait_key(KeyWait::Release, KeyPriority::Lowest, steps), None);
+        let steps: &[&[usize]] = &[&[5], &[5, 2], &[2]];
+        assert_eq!(
+            wait_key(KeyWait::Release, KeyPriority::Lowest, steps),
+            Some(2)
+        );
+    }
+
+    /// Run `cycles` instructions of `program`
+    fn run(program: &[u8], quirks: Quirks, cycles: usize) -> CPU {
+        let mut cpu = CPU::new(program, 600, 0, quirks);
+        let keys = keys(&[]);
+        for _ in 0..cycles {
+            cpu.cycle(&CycleInput::new(keys, &keys));
+        }
+        cpu
+    }
+
+    // V0 after 8016 with V0 = 3 and V1 = 5
+    fn shifted(quirks: Quirks) -> u8 {
+        run(&[0x60, 0x03, 0x61, 0x05, 0x80, 0x16], quirks, 3)
+            .registers()
+            .V[0]
+    }
+
+    // I after FX55 with X = 2 and I = 300
+    fn index_after_store(quirks: Quirks) -> usize {
+        run(&[0xA3, 0x00, 0xF2, 0x55], quirks, 2).registers().I
+    }
+
+    // Whether drawing "0" at the right edge lights the left column
+    fn wrapped(quirks: Quirks) -> bool {
+        let program = [0x60, 62, 0x61, 0x00, 0xF0, 0x29, 0xD0, 0x15];
+        run(&program, quirks, 4).gfx()[0] == PixelState::On
+    }
+
+    // PC after B300 with V0 = 2 and V3 = 4
+    fn jumped(quirks: Quirks) -> usize {
+        run(&[0x60, 0x02, 0x63, 0x04, 0xB3, 0x00], quirks, 3)
+            .registers()
+            .PC
+    }
+
+    // Whether "0" is drawn after `cycles`, the draw is the second instruction
+    // followed by a loop
+    fn drawn(quirks: Quirks, cycles: usize) -> bool {
+        run(&[0xF0, 0x29, 0xD0, 0x15, 0x12, 0x04], quirks, cycles)
+            .gfx()
+            .contains(&PixelState::On)
+    }
+
+    // VF after 8011 with VF = 5
+    fn flag_after_or(quirks: Quirks) -> u8 {
+        run(&[0x6F, 0x05, 0x80, 0x11], quirks, 2).registers().V[0xF]
+    }
+
+    #[test]
+    fn default_quirks_keep_baseline_behavior() {
+        let quirks = Quirks::default();
+        assert_eq!(shifted(quirks), 1);
+        assert_eq!(index_after_store(quirks), 0x300);
+        assert!(!wrapped(quirks));
+        assert_eq!(jumped(quirks), 0x302);
+        assert!(drawn(quirks, 2));
+        assert_eq!(flag_after_or(quirks), 5);
+    }
+
+    #[test]
+    fn shift_quirk() {
+        let quirks = Quirks {
+            shift: false,
+            ..Quirks::default()
+        };
+        assert_eq!(shifted(quirks), 2);
+    }
+
+    #[test]
+    fn memory_increment_by_x_quirk() {
+        let quirks = Quirks {
+            memory_increment_by_x: true,
+            memory_leave_i_unchanged: false,
+            ..Quirks::default()
+        };
+        assert_eq!(index_after_store(quirks), 0x302);
+    }
+
+    #[test]
+    fn memory_leave_i_unchanged_quirk() {
+        let quirks = Quirks {
+            memory_leave_i_unchanged: false,
+            ..Quirks::default()
+        };
+        assert_eq!(index_after_store(quirks), 0x303);
+    }
+
+    #[test]
+    fn wrap_quirk() {
+        let quirks = Quirks {
+            wrap: true,
+            ..Quirks::default()
+        };
+        assert!(wrapped(quirks));
+    }
+
+    #[test]
+    fn jump_quirk() {
+        let quirks = Quirks {
+            jump: true,
+            ..Quirks::default()
+        };
+        assert_eq!(jumped(quirks), 0x304);
+    }
+
+    #[test]
+    fn vblank_quirk() {
+        let quirks = Quirks {
+            vblank: true,
+            ..Quirks::default()
+        };
+        assert!(!drawn(quirks, 2));
+        // 10 instructions per frame at 600Hz
+        assert!(drawn(quirks, 12));
+    }
+
+    #[test]
+    fn logic_quirk() {
+        let quirks = Quirks {
+            logic: true,
+            ..Quirks::default()
+        };
+        assert_eq!(flag_after_or(quirks), 0);
+    }
+}
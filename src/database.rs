@@ -0,0 +1,281 @@
+use crate::quirks::Quirks;
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+
+// Platform definitions from the CHIP-8 database
+const PLATFORMS: &str = include_str!("../data/platforms.json");
+
+// Program metadata from the CHIP-8 database, used when there is no local copy
+const HASHES: &str = include_str!("../data/sha1-hashes.json");
+const PROGRAMS: &str = include_str!("../data/programs.json");
+
+// Host key and controller buttons for each of the database's named inputs
+struct Input {
+    name: &'static str,
+    key: &'static str,
+    buttons: &'static [&'static str],
+}
+
+const INPUTS: &[Input] = &[
+    Input {
+        name: "up",
+        key: "Up",
+        buttons: &["dpup", "lefty-"],
+    },
+    Input {
+        name: "down",
+        key: "Down",
+        buttons: &["dpdown", "lefty+"],
+    },
+    Input {
+        name: "left",
+        key: "Left",
+        buttons: &["dpleft", "leftx-"],
+    },
+    Input {
+        name: "right",
+        key: "Right",
+        buttons: &["dpright", "leftx+"],
+    },
+    Input {
+        name: "a",
+        key: "Space",
+        buttons: &["a"],
+    },
+    Input {
+        name: "b",
+        key: "Left Shift",
+        buttons: &["b"],
+    },
+];
+
+/// CHIP-8 variant with its quirks and speed
+pub struct Platform {
+    pub id: String,
+    pub name: String,
+    pub tickrate: Option<u64>, // Instructions per 60Hz frame
+    pub resolutions: Vec<String>,
+    quirks: Vec<(String, bool)>,
+}
+
+/// What the CHIP-8 database knows about a ROM
+pub struct RomInfo {
+    pub title: String,
+    pub authors: Vec<String>,
+    pub platform: Option<String>, // Platform ID, the first one listed
+    pub tickrate: Option<u64>,
+    pub colors: Option<(String, String)>, // Background and foreground
+    pub keys: Vec<(String, usize)>,       // Named input to CHIP-8 key
+    quirky_platforms: Vec<(String, Vec<(String, bool)>)>,
+}
+
+impl Platform {
+    /// Bundled platform by ID, e.g. "originalChip8" or "superchip"
+    pub fn find(id: &str) -> Option<Self> {
+        let platforms: Vec<Value> = serde_json::from_str(PLATFORMS).expect("bundled platforms");
+        platforms
+            .iter()
+            .find(|platform| platform["id"].as_str() == Some(id))
+            .map(|platform| Self {
+                id: id.to_string(),
+                name: platform["name"].as_str().unwrap_or(id).to_string(),
+                tickrate: platform["defaultTickrate"].as_u64(),
+                resolutions: strings(&platform["displayResolutions"]),
+                quirks: flags(&platform["quirks"]),
+            })
+    }
+
+    /// IDs of the bundled platforms
+    pub fn ids() -> Vec<String> {
+        let platforms: Vec<Value> = serde_json::from_str(PLATFORMS).expect("bundled platforms");
+        platforms
+            .iter()
+            .filter_map(|platform| platform["id"].as_str().map(str::to_string))
+            .collect()
+    }
+
+    pub fn apply(&self, quirks: &mut Quirks) {
+        for (name, value) in &self.quirks {
+            quirks.set(name, *value);
+        }
+    }
+}
+
+impl RomInfo {
+    /// Look up a ROM by SHA-1 in the bundled database
+    pub fn find(rom_hash: &str) -> Option<Self> {
+        Self::find_in(HASHES, PROGRAMS, rom_hash)
+    }
+
+    fn find_in(hashes: &str, programs: &str, rom_hash: &str) -> Option<Self> {
+        let hashes: Value = serde_json::from_str(hashes).expect("bundled hashes");
+        let index = hashes[rom_hash].as_u64()? as usize;
+        let programs: Value = serde_json::from_str(programs).expect("bundled programs");
+        Some(Self::new(&programs[index], rom_hash))
+    }
+
+    /// Look up a ROM by SHA-1 in a copy of the database's `programs.json` and
+    /// `sha1-hashes.json` in `dir`
+    pub fn lookup(dir: &Path, rom_hash: &str) -> Result<Option<Self>, String> {
+        let hashes = read_json(&dir.join("sha1-hashes.json"))?;
+        let index = match hashes[rom_hash].as_u64() {
+            Some(index) => index as usize,
+            None => return Ok(None),
+        };
+        let programs = read_json(&dir.join("programs.json"))?;
+        Ok(Some(Self::new(&programs[index], rom_hash)))
+    }
+
+    fn new(program: &Value, rom_hash: &str) -> Self {
+        let rom = &program["roms"][rom_hash];
+
+        let pixels = strings(&rom["colors"]["pixels"]);
+        let keys = match rom["keys"].as_object() {
+            Some(keys) => keys
+                .iter()
+                .filter_map(|(name, key)| Some((name.clone(), key.as_u64()? as usize)))
+                .filter(|&(_, key)| key < 16)
+                .collect(),
+            None => Vec::new(),
+        };
+        let quirky_platforms = match rom["quirkyPlatforms"].as_object() {
+            Some(platforms) => platforms
+                .iter()
+                .map(|(id, quirks)| (id.clone(), flags(quirks)))
+                .collect(),
+            None => Vec::new(),
+        };
+
+        Self {
+            title: program["title"].as_str().unwrap_or("Untitled").to_string(),
+            authors: strings(&program["authors"]),
+            platform: strings(&rom["platforms"]).into_iter().next(),
+            tickrate: rom["tickrate"].as_u64(),
+            colors: match pixels.as_slice() {
+                [background, foreground, ..] => Some((background.clone(), foreground.clone())),
+                _ => None,
+            },
+            keys,
+            quirky_platforms,
+        }
+    }
+
+    /// Quirks that differ from `platform` for this ROM
+    pub fn apply_quirks(&self, platform: &str, quirks: &mut Quirks) {
+        for (id, overrides) in &self.quirky_platforms {
+            if id == platform {
+                for (name, value) in overrides {
+                    quirks.set(name, *value);
+                }
+            }
+        }
+    }
+
+    /// Host keys for the inputs the ROM uses, by SDL key name
+    pub fn key_bindings(&self) -> Vec<(&'static str, usize)> {
+        self.inputs().map(|(input, key)| (input.key, key)).collect()
+    }
+
+    /// Controller buttons and stick directions for the inputs the ROM uses
+    pub fn pad_bindings(&self) -> Vec<(&'static str, usize)> {
+        self.inputs()
+            .flat_map(|(input, key)| input.buttons.iter().map(move |&button| (button, key)))
+            .collect()
+    }
+
+    fn inputs(&self) -> impl Iterator<Item = (&'static Input, usize)> + '_ {
+        self.keys.iter().filter_map(|(name, key)| {
+            let input = INPUTS.iter().find(|input| input.name == name)?;
+            Some((input, *key))
+        })
+    }
+}
+
+/// `database` in the user config directory, e.g. `~/.config/chip8_emu`
+pub fn default_dir() -> Option<PathBuf> {
+    Some(
+        dirs::config_dir()?
+            .join(env!("CARGO_PKG_NAME"))
+            .join("database"),
+    )
+}
+
+fn read_json(path: &Path) -> Result<Value, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("{:?}: {}", path, e))?;
+    serde_json::from_str(&text).map_err(|e| format!("{:?}: {}", path, e))
+}
+
+fn strings(value: &Value) -> Vec<String> {
+    match value.as_array() {
+        Some(values) => values
+            .iter()
+            .filter_map(|value| value.as_str().map(str::to_string))
+            .collect(),
+        None => Vec::new(),
+    }
+}
+
+fn flags(value: &Value) -> Vec<(String, bool)> {
+    match value.as_object() {
+        Some(flags) => flags
+            .iter()
+            .filter_map(|(name, flag)| Some((name.clone(), flag.as_bool()?)))
+            .collect(),
+        None => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HASHES: &str = r#"{ "0123abcd": 1 }"#;
+    const PROGRAMS: &str = r##"[
+        { "title": "Other", "roms": {} },
+        {
+            "title": "Test Game",
+            "authors": ["A. Author", "B. Author"],
+            "roms": {
+                "0123abcd": {
+                    "platforms": ["originalChip8", "modernChip8"],
+                    "tickrate": 20,
+                    "keys": { "up": 5, "a": 6, "player2Up": 1, "down": 16 },
+                    "colors": { "pixels": ["#112233", "#ffeedd"] },
+                    "quirkyPlatforms": { "originalChip8": { "vblank": false } }
+                }
+            }
+        }
+    ]"##;
+
+    #[test]
+    fn bundled_database_parses() {
+        assert!(RomInfo::find("0000000000000000000000000000000000000000").is_none());
+    }
+
+    #[test]
+    fn find_by_hash() {
+        assert!(RomInfo::find_in(HASHES, PROGRAMS, "ffff").is_none());
+        let info = RomInfo::find_in(HASHES, PROGRAMS, "0123abcd").unwrap();
+        assert_eq!(info.title, "Test Game");
+        assert_eq!(info.authors, ["A. Author", "B. Author"]);
+        assert_eq!(info.platform.as_deref(), Some("originalChip8"));
+        assert_eq!(info.tickrate, Some(20));
+        assert_eq!(
+            info.colors,
+            Some(("#112233".to_string(), "#ffeedd".to_string()))
+        );
+        assert_eq!(info.key_bindings(), [("Space", 6), ("Up", 5)]);
+        assert!(info.pad_bindings().contains(&("dpup", 5)));
+    }
+
+    #[test]
+    fn rom_quirks_override_the_platform() {
+        let info = RomInfo::find_in(HASHES, PROGRAMS, "0123abcd").unwrap();
+        let platform = Platform::find("originalChip8").unwrap();
+        let mut quirks = Quirks::default();
+        platform.apply(&mut quirks);
+        assert!(quirks.vblank);
+        info.apply_quirks(&platform.id, &mut quirks);
+        assert!(!quirks.vblank);
+    }
+}
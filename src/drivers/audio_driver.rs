@@ -1,12 +1,20 @@
+use super::Audio;
+use crate::synth::{Buzzer, Tone};
 use sdl2::audio::{AudioCallback, AudioDevice, AudioSpecDesired};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 
+/// Plays the buzzer through SDL. The device keeps running and the callback
+/// fades the tone in and out, so beeps start and stop without clicks.
 pub struct AudioDriver {
-    device: AudioDevice<SquareWave>,
+    device: AudioDevice<Beeper>,
+    beeping: Arc<AtomicBool>, // Buzzer state shared with the callback
 }
 
 impl AudioDriver {
-    pub fn new(sdl_context: &sdl2::Sdl) -> Self {
-        let audio_subsystem = sdl_context.audio().unwrap();
+    /// Fails on machines without a usable audio device
+    pub fn new(sdl_context: &sdl2::Sdl, tone: Tone) -> Result<Self, String> {
+        let audio_subsystem = sdl_context.audio()?;
 
         let desired_spec = AudioSpecDesired {
             freq: None,
@@ -14,43 +22,40 @@ impl AudioDriver {
             samples: None,     // default sample size
         };
 
-        let device = audio_subsystem
-            .open_playback(None, &desired_spec, |spec| {
-                // initialize the audio callback
-                SquareWave {
-                    phase_inc: 240.0 / spec.freq as f32,
-                    phase: 0.0,
-                    volume: 0.5,
-                }
-            })
-            .unwrap();
-
-        AudioDriver { device }
+        let beeping = Arc::new(AtomicBool::new(false));
+        let device = audio_subsystem.open_playback(None, &desired_spec, |spec| {
+            // initialize the audio callback
+            Beeper {
+                buzzer: Buzzer::new(spec.freq, tone),
+                beeping: Arc::clone(&beeping),
+            }
+        })?;
+        device.resume();
+
+        Ok(AudioDriver { device, beeping })
+    }
+}
+
+impl Audio for AudioDriver {
+    fn beep(&mut self, beep: bool) {
+        self.beeping.store(beep, Ordering::Relaxed);
     }
 
-    pub fn beep(&self, beep: bool) {
-        if beep {
-            self.device.resume();
-        } else {
-            self.device.pause();
-        }
+    fn set_tone(&mut self, tone: Tone) {
+        self.device.lock().buzzer.set_tone(tone);
     }
 }
 
-struct SquareWave {
-    phase_inc: f32,
-    phase: f32,
-    volume: f32,
+struct Beeper {
+    buzzer: Buzzer,
+    beeping: Arc<AtomicBool>,
 }
 
-impl AudioCallback for SquareWave {
+impl AudioCallback for Beeper {
     type Channel = f32;
 
     fn callback(&mut self, out: &mut [f32]) {
-        // Generate a square wave
-        for x in out.iter_mut() {
-            *x = self.volume * if self.phase < 0.5 { 1.0 } else { -1.0 };
-            self.phase = (self.phase + self.phase_inc) % 1.0;
-        }
+        let on = self.beeping.load(Ordering::Relaxed);
+        self.buzzer.fill(out, on);
     }
 }
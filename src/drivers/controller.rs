@@ -0,0 +1,116 @@
+use super::Hotkey;
+use crate::cpu::{KeyState, KEY_SIZE};
+use crate::keymap::KeyMap;
+use sdl2::controller::{Axis, Button, GameController};
+use sdl2::event::Event;
+use sdl2::GameControllerSubsystem;
+use std::collections::HashMap;
+use std::io::{stderr, Write};
+
+/// Game controllers driving the keypad. Controllers are opened as they are
+/// connected, including those present at startup.
+pub struct Controllers {
+    subsystem: Option<GameControllerSubsystem>,
+    open: HashMap<u32, GameController>, // By joystick instance ID
+    buttons: HashMap<Button, usize>,    // Button to CHIP-8 key
+    axes: Vec<(Axis, bool, usize)>,     // Stick direction, positive or negative, to CHIP-8 key
+    threshold: i16,                     // Axis value a stick direction counts as pressed from
+}
+
+impl Controllers {
+    /// `pad_map` uses SDL controller names, "a" or "dpup" for buttons and
+    /// "leftx-" or "lefty+" for stick directions. `threshold` is the fraction
+    /// of a stick's range that presses a direction.
+    pub fn new(context: &sdl2::Sdl, pad_map: &KeyMap, threshold: f32) -> Self {
+        let subsystem = match context.game_controller() {
+            Ok(subsystem) => Some(subsystem),
+            Err(e) => {
+                writeln!(&mut stderr(), "Game controllers: {}", e).ok();
+                None
+            }
+        };
+
+        let mut buttons = HashMap::new();
+        let mut axes = Vec::new();
+        for (name, key) in pad_map.bindings() {
+            let axis = name
+                .strip_suffix('+')
+                .map(|axis| (axis, true))
+                .or_else(|| name.strip_suffix('-').map(|axis| (axis, false)));
+            match axis {
+                Some((axis, positive)) => match Axis::from_string(axis) {
+                    Some(axis) => axes.push((axis, positive, key)),
+                    None => unknown(name),
+                },
+                None => match Button::from_string(name) {
+                    Some(button) => {
+                        buttons.insert(button, key);
+                    }
+                    None => unknown(name),
+                },
+            }
+        }
+
+        Self {
+            subsystem,
+            open: HashMap::new(),
+            buttons,
+            axes,
+            threshold: (threshold.clamp(0.0, 1.0) * f32::from(i16::MAX)) as i16,
+        }
+    }
+
+    /// Open and close controllers as they are plugged in and out, returns a
+    /// hotkey for unmapped buttons that have one
+    pub fn handle_event(&mut self, event: &Event) -> Option<Hotkey> {
+        match *event {
+            Event::ControllerDeviceAdded { which, .. } => {
+                match self.subsystem.as_ref()?.open(which) {
+                    Ok(controller) => {
+                        self.open.insert(controller.instance_id(), controller);
+                    }
+                    Err(e) => {
+                        writeln!(&mut stderr(), "Game controller {}: {}", which, e).ok();
+                    }
+                }
+                None
+            }
+            Event::ControllerDeviceRemoved { which, .. } => {
+                self.open.remove(&which);
+                None
+            }
+            Event::ControllerButtonDown { button, .. } if !self.buttons.contains_key(&button) => {
+                match button {
+                    Button::Start => Some(Hotkey::Pause),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// Press the keys held on any controller
+    pub fn read_keys(&self, keys: &mut [KeyState; KEY_SIZE]) {
+        for controller in self.open.values() {
+            for (&button, &key) in &self.buttons {
+                if controller.button(button) {
+                    keys[key] = KeyState::Pressed;
+                }
+            }
+            for &(axis, positive, key) in &self.axes {
+                let value = controller.axis(axis);
+                let pressed = match positive {
+                    true => value > self.threshold,
+                    false => value < -self.threshold,
+                };
+                if pressed {
+                    keys[key] = KeyState::Pressed;
+                }
+            }
+        }
+    }
+}
+
+fn unknown(name: &str) {
+    writeln!(&mut stderr(), "Pad map: unknown button or axis {:?}", name).ok();
+}
@@ -0,0 +1,227 @@
+use super::keypad::LAYOUT;
+use super::text::{draw_text, GLYPH_SIZE};
+use super::{Click, DebugView};
+use crate::cpu::KeyState;
+use crate::disasm::disassemble;
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::Canvas;
+use sdl2::video::Window;
+use std::time::{Duration, Instant};
+
+const TITLE: &str = "CHIP-8 Debugger";
+
+// The debugger is text only, 30Hz is plenty
+const DRAW_INTERVAL: Duration = Duration::from_millis(33);
+
+// Layout in output pixels, text is drawn on a grid of character cells
+const TEXT_SCALE: u32 = 2;
+const CHAR_W: i32 = (GLYPH_SIZE * TEXT_SCALE) as i32;
+const LINE_HEIGHT: i32 = CHAR_W + 2;
+const MARGIN: i32 = 16;
+const DISASM_X: i32 = MARGIN + 25 * CHAR_W;
+const DISASM_LINES: usize = 20;
+const DISASM_BEFORE: usize = 8; // Instructions shown before PC
+const MEMORY_Y: i32 = MARGIN + 21 * LINE_HEIGHT;
+const MEMORY_LINES: usize = 12;
+const MEMORY_BEFORE: usize = 4; // Lines shown before the one holding I
+const WINDOW_W: u32 = 960;
+const WINDOW_H: u32 = 640;
+
+const TEXT: Color = Color::RGB(0xD0, 0xD0, 0xD0);
+const HEADING: Color = Color::RGB(0x80, 0x80, 0x80);
+const HIGHLIGHT: Color = Color::RGB(0x28, 0x40, 0x78);
+const BREAKPOINT: Color = Color::RGB(0xE0, 0x40, 0x40);
+const PRESSED: Color = Color::RGB(0x30, 0x80, 0x30);
+
+/// Window showing registers, keypad, disassembly and memory. Clicking a
+/// disassembly line toggles a breakpoint on it.
+pub struct Debugger {
+    canvas: Canvas<Window>,
+    disasm: Vec<usize>, // Address of each disassembly line last drawn
+    last_draw: Option<Instant>,
+}
+
+impl Debugger {
+    pub fn new(video_subsystem: &sdl2::VideoSubsystem) -> Result<Self, String> {
+        let window = video_subsystem
+            .window(TITLE, WINDOW_W, WINDOW_H)
+            .build()
+            .map_err(|e| e.to_string())?;
+        let canvas = window
+            .into_canvas()
+            .accelerated()
+            .build()
+            .map_err(|e| e.to_string())?;
+
+        Ok(Self {
+            canvas,
+            disasm: Vec::new(),
+            last_draw: None,
+        })
+    }
+
+    pub fn window_id(&self) -> u32 {
+        self.canvas.window().id()
+    }
+
+    pub fn draw(&mut self, view: &DebugView) {
+        let now = Instant::now();
+        if self
+            .last_draw
+            .is_some_and(|last| now - last < DRAW_INTERVAL)
+        {
+            return;
+        }
+        self.last_draw = Some(now);
+
+        self.canvas.set_draw_color(Color::RGB(0, 0, 0));
+        self.canvas.clear();
+        self.draw_registers(view);
+        self.draw_disassembly(view);
+        self.draw_memory(view);
+        self.canvas.present();
+    }
+
+    /// Address of the disassembly line at a click in this window
+    pub fn line_at(&self, click: Click) -> Option<usize> {
+        if click.window_id != self.window_id() || click.x < DISASM_X || click.y < MARGIN {
+            return None;
+        }
+        let line = ((click.y - MARGIN) / LINE_HEIGHT) as usize;
+        self.disasm.get(line).copied()
+    }
+
+    fn draw_registers(&mut self, view: &DebugView) {
+        let regs = &view.regs;
+        let mut lines = vec![
+            (format!("PC {:03X}   I  {:03X}", regs.PC, regs.I), TEXT),
+            (
+                format!("DT {:02X}    ST {:02X}", regs.delay_timer, regs.sound_timer),
+                TEXT,
+            ),
+            (String::new(), TEXT),
+        ];
+        for (row, values) in regs.V.chunks(4).enumerate() {
+            let line: Vec<_> = values
+                .iter()
+                .enumerate()
+                .map(|(i, v)| format!("V{:X} {:02X}", row * 4 + i, v))
+                .collect();
+            lines.push((line.join(" "), TEXT));
+        }
+        lines.push((String::new(), TEXT));
+        lines.push((format!("Stack (SP {:X})", regs.SP), HEADING));
+        for entries in regs.stack[1..=regs.SP].chunks(4) {
+            let line: Vec<_> = entries.iter().map(|a| format!("{:03X}", a)).collect();
+            lines.push((line.join("  "), TEXT));
+        }
+        lines.push((String::new(), TEXT));
+        lines.push(("Keypad".to_string(), HEADING));
+
+        for (row, (line, color)) in lines.iter().enumerate() {
+            draw_text(
+                &mut self.canvas,
+                MARGIN,
+                line_y(row),
+                TEXT_SCALE,
+                line,
+                *color,
+            );
+        }
+
+        // Pressed keys are highlighted
+        let top = lines.len();
+        for (row, keys) in LAYOUT.iter().enumerate() {
+            for (col, &key) in keys.iter().enumerate() {
+                let x = MARGIN + col as i32 * 3 * CHAR_W;
+                let y = line_y(top + row);
+                if view.keys[key] == KeyState::Pressed {
+                    self.canvas.set_draw_color(PRESSED);
+                    let _ = self.canvas.fill_rect(Rect::new(
+                        x - CHAR_W / 2,
+                        y - 1,
+                        2 * CHAR_W as u32,
+                        LINE_HEIGHT as u32,
+                    ));
+                }
+                let text = format!("{:X}", key);
+                draw_text(&mut self.canvas, x, y, TEXT_SCALE, &text, TEXT);
+            }
+        }
+    }
+
+    /// Instructions around PC, assuming they are aligned with it
+    fn draw_disassembly(&mut self, view: &DebugView) {
+        let pc = view.regs.PC;
+        let first = pc.saturating_sub(2 * DISASM_BEFORE) | (pc & 1);
+        self.disasm = (first..view.memory.len() - 1)
+            .step_by(2)
+            .take(DISASM_LINES)
+            .collect();
+
+        for (row, &addr) in self.disasm.iter().enumerate() {
+            let y = line_y(row);
+            if addr == pc {
+                self.canvas.set_draw_color(HIGHLIGHT);
+                let _ = self.canvas.fill_rect(Rect::new(
+                    DISASM_X,
+                    y - 1,
+                    WINDOW_W - (DISASM_X + MARGIN) as u32,
+                    LINE_HEIGHT as u32,
+                ));
+            }
+            if view.breakpoints.contains(&addr) {
+                draw_text(&mut self.canvas, DISASM_X, y, TEXT_SCALE, "*", BREAKPOINT);
+            }
+
+            let instruction = u16::from(view.memory[addr]) << 8 | u16::from(view.memory[addr + 1]);
+            let line = format!(
+                "{:04X}  {:04X}  {}",
+                addr,
+                instruction,
+                disassemble(instruction)
+            );
+            draw_text(
+                &mut self.canvas,
+                DISASM_X + 2 * CHAR_W,
+                y,
+                TEXT_SCALE,
+                &line,
+                TEXT,
+            );
+        }
+    }
+
+    /// Hex dump around I, with the byte at I highlighted
+    fn draw_memory(&mut self, view: &DebugView) {
+        let last = view.memory.len() / 16 - MEMORY_LINES;
+        let first = (view.regs.I / 16).saturating_sub(MEMORY_BEFORE).min(last);
+
+        for row in 0..MEMORY_LINES {
+            let start = (first + row) * 16;
+            let y = MEMORY_Y + row as i32 * LINE_HEIGHT;
+            if (start..start + 16).contains(&view.regs.I) {
+                let x = MARGIN + (6 + 3 * (view.regs.I - start) as i32) * CHAR_W;
+                self.canvas.set_draw_color(HIGHLIGHT);
+                let _ = self.canvas.fill_rect(Rect::new(
+                    x - CHAR_W / 2,
+                    y - 1,
+                    3 * CHAR_W as u32,
+                    LINE_HEIGHT as u32,
+                ));
+            }
+
+            let bytes: Vec<_> = view.memory[start..start + 16]
+                .iter()
+                .map(|b| format!("{:02X}", b))
+                .collect();
+            let line = format!("{:04X}  {}", start, bytes.join(" "));
+            draw_text(&mut self.canvas, MARGIN, y, TEXT_SCALE, &line, TEXT);
+        }
+    }
+}
+
+fn line_y(row: usize) -> i32 {
+    MARGIN + row as i32 * LINE_HEIGHT
+}
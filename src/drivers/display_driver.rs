@@ -1,28 +1,60 @@
-use crate::cpu::{PixelState, DISPLAY_H, DISPLAY_W};
-use sdl2::pixels::Color;
+use super::debugger::Debugger;
+use super::keypad::{Keypad, KEYPAD_SHARE};
+use super::osd::Osd;
+use super::sprite_viewer::SpriteViewer;
+use super::{Click, DebugView, Scroll, Stats, ToolAction, Video};
+use crate::cpu::{KeyState, Registers, DISPLAY_H, DISPLAY_W, KEY_SIZE};
+use crate::palette::Palette;
+use sdl2::pixels::{Color, PixelFormatEnum};
 use sdl2::rect::Rect;
-use sdl2::render::Canvas;
-use sdl2::video::Window;
+use sdl2::render::{Canvas, Texture};
+use sdl2::video::{FullscreenType, Window};
+use std::time::Duration;
 
-const PIXEL_SIZE: usize = 20;
 const TITLE_PREFIX: &str = "CHIP-8";
 
+// Limit window refresh rate to 100Hz
+const DRAW_INTERVAL: Duration = Duration::from_millis(10);
+
 pub struct DisplayDriver {
     canvas: Canvas<Window>,
+    texture: Texture, // Streaming texture holding the framebuffer
+    rgba: Vec<u8>,    // Framebuffer contents last uploaded to the texture
+    scratch: Vec<u8>,
+    palette: Palette,
+    integer_scaling: bool,
+    osd: Osd,
+    video_subsystem: sdl2::VideoSubsystem,
+    debugger: Option<Debugger>,
+    sprite_viewer: Option<SpriteViewer>,
+    keypad: Option<Keypad>,
+    keypad_changed: bool,
 }
 
 impl DisplayDriver {
-    pub fn new(context: &sdl2::Sdl) -> Self {
+    pub fn new(
+        context: &sdl2::Sdl,
+        palette: Palette,
+        scale: u32,
+        integer_scaling: bool,
+        show_stats: bool,
+        keypad: bool,
+    ) -> Self {
         sdl2::hint::set("SDL_VIDEO_X11_NET_WM_BYPASS_COMPOSITOR", "0");
+        sdl2::hint::set("SDL_RENDER_SCALE_QUALITY", "nearest");
+
+        // The keypad gets its share of the window below the display
+        let display_h = scale * DISPLAY_H as u32;
+        let window_h = match keypad {
+            true => display_h * KEYPAD_SHARE / (KEYPAD_SHARE - 1),
+            false => display_h,
+        };
 
         let video_subsystem = context.video().unwrap();
         let window = video_subsystem
-            .window(
-                TITLE_PREFIX,
-                (PIXEL_SIZE * DISPLAY_W) as u32,
-                (PIXEL_SIZE * DISPLAY_H) as u32,
-            )
+            .window(TITLE_PREFIX, scale * DISPLAY_W as u32, window_h)
             .position_centered()
+            .resizable()
             .vulkan()
             .build()
             .unwrap();
@@ -38,37 +70,191 @@ impl DisplayDriver {
         canvas.clear();
         canvas.present();
 
-        Self { canvas }
+        let texture = canvas
+            .texture_creator()
+            .create_texture_streaming(PixelFormatEnum::RGBA32, DISPLAY_W as u32, DISPLAY_H as u32)
+            .unwrap();
+
+        // Start out different from any frame so the first one is uploaded
+        let rgba = vec![0; DISPLAY_W * DISPLAY_H * 4];
+        let scratch = rgba.clone();
+
+        Self {
+            canvas,
+            texture,
+            rgba,
+            scratch,
+            palette,
+            integer_scaling,
+            osd: Osd::new(show_stats),
+            video_subsystem,
+            debugger: None,
+            sprite_viewer: None,
+            keypad: keypad.then(|| Keypad::new(palette)),
+            keypad_changed: false,
+        }
     }
+}
 
-    pub fn draw(&mut self, gfx: &[PixelState], perf: Option<usize>) {
-        for y in 0..DISPLAY_H {
-            for x in 0..DISPLAY_W {
-                let offset = y * DISPLAY_W + x;
-                let color = match gfx[offset] {
-                    PixelState::On => Color::RGB(255, 255, 255),
-                    PixelState::Off => Color::RGB(0, 0, 0),
-                };
-
-                let pix_x: i32 = (x * PIXEL_SIZE) as i32;
-                let pix_y: i32 = (y * PIXEL_SIZE) as i32;
-                self.canvas.set_draw_color(color);
-                let _ = self.canvas.fill_rect(Rect::new(
-                    pix_x,
-                    pix_y,
-                    PIXEL_SIZE as u32,
-                    PIXEL_SIZE as u32,
-                ));
-            }
+impl Video for DisplayDriver {
+    fn draw_interval(&self) -> Duration {
+        DRAW_INTERVAL
+    }
+
+    fn draw(&mut self, pixels: Option<&[f32]>, _regs: &Registers, stats: Option<Stats>) {
+        if let Some(stats) = stats {
+            self.osd.set_stats(stats);
         }
 
-        if let Some(perf) = perf {
-            let _ = self
-                .canvas
-                .window_mut()
-                .set_title(format!("{} | IPS: {}", TITLE_PREFIX, perf).as_str());
+        // The window only needs presenting when the display, OSD or keypad
+        // changed
+        let osd_changed = self.osd.update();
+        let keypad_changed = std::mem::take(&mut self.keypad_changed);
+        if pixels.is_none() && !osd_changed && !keypad_changed {
+            return;
         }
 
+        // Letterbox the display into the current window size, above the
+        // keypad
+        let (out_w, out_h) = self.canvas.output_size().unwrap_or((0, 0));
+        let keypad_h = match self.keypad {
+            Some(_) => out_h / KEYPAD_SHARE,
+            None => 0,
+        };
+        let view = viewport(
+            out_w,
+            out_h - keypad_h,
+            DISPLAY_W,
+            DISPLAY_H,
+            self.integer_scaling,
+        );
+        self.canvas.set_draw_color(Color::RGB(0, 0, 0));
+        self.canvas.clear();
+
+        // Only upload frames that changed, SDL scales the texture
+        if let Some(pixels) = pixels {
+            self.palette.fill_rgba(pixels, &mut self.scratch);
+            if self.scratch != self.rgba {
+                std::mem::swap(&mut self.scratch, &mut self.rgba);
+                let _ = self.texture.update(None, &self.rgba, DISPLAY_W * 4);
+            }
+        }
+        let _ = self.canvas.copy(&self.texture, None, view);
+        if let Some(keypad) = &mut self.keypad {
+            let area = Rect::new(0, (out_h - keypad_h) as i32, out_w.max(1), keypad_h.max(1));
+            keypad.render(&mut self.canvas, area);
+        }
+        self.osd.render(&mut self.canvas);
+
         self.canvas.present();
     }
+
+    fn update_keypad(&mut self, keys: &[KeyState; KEY_SIZE], polled: u16) {
+        if let Some(keypad) = &mut self.keypad {
+            self.keypad_changed |= keypad.update(keys, polled);
+        }
+    }
+
+    fn keypad_key(&self, pointer: Click) -> Option<usize> {
+        if pointer.window_id != self.canvas.window().id() {
+            return None;
+        }
+        self.keypad.as_ref()?.key_at(pointer.x, pointer.y)
+    }
+
+    fn toggle_fullscreen(&mut self) {
+        let window = self.canvas.window_mut();
+        let fullscreen = match window.fullscreen_state() {
+            FullscreenType::Off => FullscreenType::Desktop,
+            _ => FullscreenType::Off,
+        };
+        let _ = window.set_fullscreen(fullscreen);
+    }
+
+    fn show_message(&mut self, message: &str) {
+        self.osd.message(message);
+    }
+
+    fn toggle_stats(&mut self) {
+        self.osd.toggle_stats();
+    }
+
+    fn toggle_debugger(&mut self) {
+        if self.debugger.take().is_none() {
+            match Debugger::new(&self.video_subsystem) {
+                Ok(debugger) => self.debugger = Some(debugger),
+                Err(e) => self.osd.message(&format!("Debugger: {}", e)),
+            }
+        }
+    }
+
+    fn toggle_sprite_viewer(&mut self) {
+        if self.sprite_viewer.take().is_none() {
+            match SpriteViewer::new(&self.video_subsystem, self.palette) {
+                Ok(viewer) => self.sprite_viewer = Some(viewer),
+                Err(e) => self.osd.message(&format!("Sprite viewer: {}", e)),
+            }
+        }
+    }
+
+    fn draw_tools(&mut self, view: &DebugView) {
+        if let Some(debugger) = &mut self.debugger {
+            debugger.draw(view);
+        }
+        if let Some(viewer) = &mut self.sprite_viewer {
+            viewer.draw(view);
+        }
+    }
+
+    fn click(&mut self, click: Click) -> Option<ToolAction> {
+        if let Some(addr) = self.debugger.as_ref().and_then(|d| d.line_at(click)) {
+            return Some(ToolAction::ToggleBreakpoint(addr));
+        }
+        self.sprite_viewer.as_mut()?.click(click)
+    }
+
+    fn scroll(&mut self, scroll: Scroll) {
+        if let Some(viewer) = &mut self.sprite_viewer {
+            viewer.scroll(scroll.window_id, scroll.y);
+        }
+    }
+
+    fn close_window(&mut self, window_id: u32) -> bool {
+        if self
+            .debugger
+            .as_ref()
+            .is_some_and(|d| d.window_id() == window_id)
+        {
+            self.debugger = None;
+        } else if self
+            .sprite_viewer
+            .as_ref()
+            .is_some_and(|v| v.window_id() == window_id)
+        {
+            self.sprite_viewer = None;
+        } else {
+            return false;
+        }
+        true
+    }
+}
+
+/// Largest area with the display's aspect ratio centered in the output. With
+/// `integer` the scale is rounded down to a whole multiple of the resolution.
+fn viewport(out_w: u32, out_h: u32, width: usize, height: usize, integer: bool) -> Rect {
+    let (width, height) = (width as u32, height as u32);
+    let (w, h) = if integer {
+        let scale = std::cmp::max(1, std::cmp::min(out_w / width, out_h / height));
+        (width * scale, height * scale)
+    } else if out_w * height > out_h * width {
+        (out_h * width / height, out_h)
+    } else {
+        (out_w, out_w * height / width)
+    };
+    Rect::new(
+        (out_w as i32 - w as i32) / 2,
+        (out_h as i32 - h as i32) / 2,
+        w.max(1),
+        h.max(1),
+    )
 }
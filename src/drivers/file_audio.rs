@@ -0,0 +1,109 @@
+use super::Audio;
+use crate::synth::{sample_count, Buzzer, Tone, SAMPLE_RATE};
+use std::io::{stderr, Write};
+use std::time::Duration;
+
+/// Writes the buzzer as raw 32 bit float mono samples at 44.1kHz instead of
+/// playing it, generated from emulated time so the output is deterministic
+pub struct FileAudio<W: Write> {
+    writer: Option<W>, // `None` after a write error
+    buzzer: Buzzer,
+    beep: bool,
+    samples: Vec<f32>,
+    written: u64,
+}
+
+impl<W: Write> FileAudio<W> {
+    pub fn new(writer: W, tone: Tone) -> Self {
+        Self {
+            writer: Some(writer),
+            buzzer: Buzzer::new(SAMPLE_RATE as i32, tone),
+            beep: false,
+            samples: Vec::new(),
+            written: 0,
+        }
+    }
+}
+
+impl<W: Write> Audio for FileAudio<W> {
+    fn beep(&mut self, beep: bool) {
+        self.beep = beep;
+    }
+
+    fn set_tone(&mut self, tone: Tone) {
+        self.buzzer.set_tone(tone);
+    }
+
+    fn sync(&mut self, elapsed: Duration) {
+        let end = sample_count(elapsed, SAMPLE_RATE);
+        let writer = match &mut self.writer {
+            Some(writer) if end > self.written => writer,
+            _ => return,
+        };
+
+        self.samples.resize((end - self.written) as usize, 0.0);
+        self.buzzer.fill(&mut self.samples, self.beep);
+        let bytes: Vec<u8> = self
+            .samples
+            .iter()
+            .flat_map(|sample| sample.to_le_bytes())
+            .collect();
+        if let Err(e) = writer.write_all(&bytes) {
+            writeln!(&mut stderr(), "Audio output: {}", e).ok();
+            self.writer = None;
+        }
+        self.written = end;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::synth::Waveform;
+
+    const FRAME: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
+    fn samples(bytes: &[u8]) -> Vec<f32> {
+        bytes
+            .chunks(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect()
+    }
+
+    #[test]
+    fn writes_samples_for_emulated_time() {
+        let tone = Tone {
+            frequency: 440.0,
+            waveform: Waveform::Square,
+            volume: 1.0,
+            muted: false,
+        };
+        let mut audio = FileAudio::new(Vec::new(), tone);
+        audio.sync(FRAME);
+        audio.beep(true);
+        audio.sync(FRAME * 2);
+        audio.beep(false);
+        audio.sync(FRAME * 4);
+
+        let samples = samples(audio.writer.as_ref().unwrap());
+        assert_eq!(samples.len(), 4 * 735);
+        assert!(samples[..735].iter().all(|&s| s == 0.0));
+        assert!(samples[735..2 * 735].iter().any(|&s| s != 0.0));
+        // Silent again once the release envelope has finished
+        assert!(samples[3 * 735..].iter().all(|&s| s == 0.0));
+    }
+
+    #[test]
+    fn sync_backwards_writes_nothing() {
+        let tone = Tone {
+            frequency: 440.0,
+            waveform: Waveform::Sine,
+            volume: 1.0,
+            muted: false,
+        };
+        let mut audio = FileAudio::new(Vec::new(), tone);
+        audio.sync(FRAME * 2);
+        audio.sync(FRAME);
+        assert_eq!(audio.writer.as_ref().unwrap().len(), 2 * 735 * 4);
+    }
+}
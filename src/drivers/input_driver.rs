@@ -1,37 +1,94 @@
+use super::controller::Controllers;
+use super::{Click, Input, InputState, Scroll};
 use crate::cpu::{KeyState, KEY_SIZE};
-use clap::arg_enum;
-use sdl2::event::Event;
+use crate::hotkeys::{Action, HotkeyMap};
+use crate::keymap::KeyMap;
+use sdl2::event::{Event, WindowEvent};
 use sdl2::keyboard::Keycode;
-
-arg_enum! {
-    #[derive(PartialEq)]
-    pub enum KeyMapping {
-        Literal,
-        QWERTY,
-    }
-}
-
-const MAPPING: KeyMapping = KeyMapping::QWERTY;
+use sdl2::mouse::MouseButton;
+use std::collections::HashMap;
+use std::io::{stderr, Write};
 
 pub struct InputDriver {
     events: sdl2::EventPump,
+    keys: HashMap<Keycode, usize>, // Host key to CHIP-8 key
+    hotkeys: HashMap<Keycode, Action>,
+    controllers: Controllers,
+    pointer: Option<Click>, // Left mouse button held down, touches arrive as mouse events
 }
 
 impl InputDriver {
-    pub fn new(context: &sdl2::Sdl) -> Self {
+    pub fn new(
+        context: &sdl2::Sdl,
+        key_map: &KeyMap,
+        hotkey_map: &HotkeyMap,
+        pad_map: &KeyMap,
+        threshold: f32,
+    ) -> Self {
         Self {
             events: context.event_pump().unwrap(),
+            keys: keycodes("Key map", key_map.bindings()),
+            hotkeys: keycodes("Hotkeys", hotkey_map.bindings()),
+            controllers: Controllers::new(context, pad_map, threshold),
+            pointer: None,
         }
     }
+}
 
-    pub fn poll(&mut self) -> Result<[KeyState; KEY_SIZE], ()> {
+impl Input for InputDriver {
+    fn poll(&mut self) -> Result<InputState, ()> {
+        let mut hotkeys = Vec::new();
+        let mut redraw = false;
+        let mut clicks = Vec::new();
+        let mut scrolls = Vec::new();
+        let mut closed = Vec::new();
         for event in self.events.poll_iter() {
+            if let Some(hotkey) = self.controllers.handle_event(&event) {
+                hotkeys.push(hotkey);
+            }
             match event {
                 Event::Quit { .. } => return Err(()),
+                Event::Window {
+                    win_event: WindowEvent::SizeChanged(..) | WindowEvent::Exposed,
+                    ..
+                } => redraw = true,
+                Event::Window {
+                    window_id,
+                    win_event: WindowEvent::Close,
+                    ..
+                } => closed.push(window_id),
+                Event::MouseButtonDown {
+                    window_id,
+                    mouse_btn: MouseButton::Left,
+                    x,
+                    y,
+                    ..
+                } => {
+                    clicks.push(Click { window_id, x, y });
+                    self.pointer = Some(Click { window_id, x, y });
+                }
+                Event::MouseMotion {
+                    window_id,
+                    mousestate,
+                    x,
+                    y,
+                    ..
+                } if mousestate.left() => self.pointer = Some(Click { window_id, x, y }),
+                Event::MouseButtonUp {
+                    mouse_btn: MouseButton::Left,
+                    ..
+                } => self.pointer = None,
+                Event::MouseWheel { window_id, y, .. } => scrolls.push(Scroll { window_id, y }),
+                // Keys mapped to the keypad are never hotkeys
                 Event::KeyDown {
-                    keycode: Some(Keycode::Escape),
+                    keycode: Some(key),
+                    repeat: false,
                     ..
-                } => return Err(()),
+                } if !self.keys.contains_key(&key) => {
+                    if let Some(&Action::Press(hotkey)) = self.hotkeys.get(&key) {
+                        hotkeys.push(hotkey);
+                    }
+                }
                 _ => (),
             }
         }
@@ -45,63 +102,45 @@ impl InputDriver {
             .filter_map(Keycode::from_scancode)
             .collect();
 
+        self.controllers.read_keys(&mut chip8_keys);
+
+        let mut fast_forward = false;
         for key in keys {
-            if let Some(idx) = mapping(key) {
+            if let Some(&idx) = self.keys.get(&key) {
                 chip8_keys[idx] = KeyState::Pressed;
+            } else if self.hotkeys.get(&key) == Some(&Action::FastForward) {
+                fast_forward = true;
             }
         }
 
-        Ok(chip8_keys)
-    }
-}
-
-fn mapping(key: Keycode) -> Option<usize> {
-    match MAPPING {
-        KeyMapping::Literal => mapping_literal(key),
-        KeyMapping::QWERTY => mapping_qwerty(key),
-    }
-}
-
-fn mapping_literal(key: Keycode) -> Option<usize> {
-    match key {
-        Keycode::Num0 | Keycode::Kp0 => Some(0x0),
-        Keycode::Num1 | Keycode::Kp1 => Some(0x1),
-        Keycode::Num2 | Keycode::Kp2 => Some(0x2),
-        Keycode::Num3 | Keycode::Kp3 => Some(0x3),
-        Keycode::Num4 | Keycode::Kp4 => Some(0x4),
-        Keycode::Num5 | Keycode::Kp5 => Some(0x5),
-        Keycode::Num6 | Keycode::Kp6 => Some(0x6),
-        Keycode::Num7 | Keycode::Kp7 => Some(0x7),
-        Keycode::Num8 | Keycode::Kp8 => Some(0x8),
-        Keycode::Num9 | Keycode::Kp9 => Some(0x9),
-        Keycode::A => Some(0xA),
-        Keycode::B => Some(0xB),
-        Keycode::C => Some(0xC),
-        Keycode::D => Some(0xD),
-        Keycode::E => Some(0xE),
-        Keycode::F => Some(0xF),
-        _ => None,
+        Ok(InputState {
+            keys: chip8_keys,
+            hotkeys,
+            fast_forward,
+            redraw,
+            clicks,
+            scrolls,
+            closed,
+            pointer: self.pointer,
+        })
     }
 }
 
-fn mapping_qwerty(key: Keycode) -> Option<usize> {
-    match key {
-        Keycode::Num1 => Some(0x1),
-        Keycode::Num2 => Some(0x2),
-        Keycode::Num3 => Some(0x3),
-        Keycode::Num4 => Some(0xC),
-        Keycode::Q => Some(0x4),
-        Keycode::W => Some(0x5),
-        Keycode::E => Some(0x6),
-        Keycode::R => Some(0xD),
-        Keycode::A => Some(0x7),
-        Keycode::S => Some(0x8),
-        Keycode::D => Some(0x9),
-        Keycode::F => Some(0xE),
-        Keycode::Z => Some(0xA),
-        Keycode::X => Some(0x0),
-        Keycode::C => Some(0xB),
-        Keycode::V => Some(0xF),
-        _ => None,
+/// Resolve SDL key names, warning about unknown ones
+fn keycodes<'a, T>(
+    what: &str,
+    bindings: impl Iterator<Item = (&'a str, T)>,
+) -> HashMap<Keycode, T> {
+    let mut keycodes = HashMap::new();
+    for (name, value) in bindings {
+        match Keycode::from_name(name) {
+            Some(keycode) => {
+                keycodes.insert(keycode, value);
+            }
+            None => {
+                writeln!(&mut stderr(), "{}: unknown key {:?}", what, name).ok();
+            }
+        }
     }
+    keycodes
 }
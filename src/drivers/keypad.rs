@@ -0,0 +1,117 @@
+use super::text::{draw_text, text_width, GLYPH_SIZE};
+use crate::cpu::{KeyState, KEY_SIZE};
+use crate::palette::Palette;
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::Canvas;
+use sdl2::video::Window;
+
+/// Keys in their positions on the COSMAC VIP keypad
+pub const LAYOUT: [[usize; 4]; 4] = [
+    [0x1, 0x2, 0x3, 0xC],
+    [0x4, 0x5, 0x6, 0xD],
+    [0x7, 0x8, 0x9, 0xE],
+    [0xA, 0x0, 0xB, 0xF],
+];
+
+// The keypad takes 1/KEYPAD_SHARE of the window height, below the display
+pub const KEYPAD_SHARE: u32 = 3;
+
+const GAP: i32 = 4;
+const KEY: Color = Color::RGB(0x30, 0x30, 0x30);
+const TEXT: Color = Color::RGB(0xD0, 0xD0, 0xD0);
+const POLLED: Color = Color::RGB(0x40, 0x70, 0xE0); // Tested by the ROM this frame
+
+/// 4x4 hex keypad drawn under the display that can be pressed with the mouse
+/// or a touch screen. Keys the ROM tests with EX9E/EXA1 are outlined.
+pub struct Keypad {
+    palette: Palette,
+    keys: [KeyState; KEY_SIZE],
+    polled: u16, // Bit N is set when key N was tested
+    area: Rect,  // Keypad as last drawn, in output pixels
+}
+
+impl Keypad {
+    pub fn new(palette: Palette) -> Self {
+        Self {
+            palette,
+            keys: [KeyState::NotPressed; KEY_SIZE],
+            polled: 0,
+            area: Rect::new(0, 0, 1, 1),
+        }
+    }
+
+    /// Change the keys shown, returns `true` if the keypad needs redrawing
+    pub fn update(&mut self, keys: &[KeyState; KEY_SIZE], polled: u16) -> bool {
+        let changed = self.keys != *keys || self.polled != polled;
+        self.keys = *keys;
+        self.polled = polled;
+        changed
+    }
+
+    /// Key under a point in output pixels
+    pub fn key_at(&self, x: i32, y: i32) -> Option<usize> {
+        if !self.area.contains_point((x, y)) {
+            return None;
+        }
+        let cell = self.area.height() as i32 / 4;
+        let row = ((y - self.area.y()) / cell.max(1)).min(3);
+        let col = ((x - self.area.x()) / cell.max(1)).min(3);
+        Some(LAYOUT[row as usize][col as usize])
+    }
+
+    /// Draw square keys centered in `area`
+    pub fn render(&mut self, canvas: &mut Canvas<Window>, area: Rect) {
+        let cell = std::cmp::min(area.width() / 4, area.height() / 4).max(1);
+        self.area = Rect::new(
+            area.x() + (area.width() - 4 * cell) as i32 / 2,
+            area.y() + (area.height() - 4 * cell) as i32 / 2,
+            4 * cell,
+            4 * cell,
+        );
+        let text_scale = (cell / (3 * GLYPH_SIZE)).max(1);
+        let (fg, bg) = (self.palette.foreground, self.palette.background);
+
+        for (row, keys) in LAYOUT.iter().enumerate() {
+            for (col, &key) in keys.iter().enumerate() {
+                let rect = Rect::new(
+                    self.area.x() + (col as u32 * cell) as i32 + GAP / 2,
+                    self.area.y() + (row as u32 * cell) as i32 + GAP / 2,
+                    cell.saturating_sub(GAP as u32).max(1),
+                    cell.saturating_sub(GAP as u32).max(1),
+                );
+                let (fill, text) = match self.keys[key] {
+                    KeyState::Pressed => {
+                        (Color::RGB(fg.r, fg.g, fg.b), Color::RGB(bg.r, bg.g, bg.b))
+                    }
+                    KeyState::NotPressed => (KEY, TEXT),
+                };
+                canvas.set_draw_color(fill);
+                let _ = canvas.fill_rect(rect);
+                if self.polled & (1 << key) != 0 {
+                    canvas.set_draw_color(POLLED);
+                    let _ = canvas.draw_rects(&[rect, shrink(rect, 1), shrink(rect, 2)]);
+                }
+
+                let label = format!("{:X}", key);
+                draw_text(
+                    canvas,
+                    rect.center().x() - text_width(&label, text_scale) as i32 / 2,
+                    rect.center().y() - (GLYPH_SIZE * text_scale) as i32 / 2,
+                    text_scale,
+                    &label,
+                    text,
+                );
+            }
+        }
+    }
+}
+
+fn shrink(rect: Rect, by: u32) -> Rect {
+    Rect::new(
+        rect.x() + by as i32,
+        rect.y() + by as i32,
+        rect.width().saturating_sub(2 * by).max(1),
+        rect.height().saturating_sub(2 * by).max(1),
+    )
+}
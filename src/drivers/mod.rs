@@ -1,7 +1,176 @@
-mod display_driver;
 mod audio_driver;
+mod controller;
+mod debugger;
+mod display_driver;
+mod file_audio;
 mod input_driver;
+mod keypad;
+mod null_driver;
+mod osd;
+mod sprite_viewer;
+mod terminal_driver;
+mod text;
 
-pub use self::display_driver::DisplayDriver;
 pub use self::audio_driver::AudioDriver;
-pub use self::input_driver::{InputDriver, KeyMapping};
+pub use self::display_driver::DisplayDriver;
+pub use self::file_audio::FileAudio;
+pub use self::input_driver::InputDriver;
+pub use self::null_driver::{NullAudio, NullInput, NullVideo};
+pub use self::terminal_driver::{
+    Glyphs, TerminalAudio, TerminalInput, TerminalSession, TerminalVideo,
+};
+
+use crate::cpu::{KeyState, Registers, KEY_SIZE};
+use crate::sprite::Sprite;
+use crate::synth::Tone;
+use std::collections::BTreeSet;
+use std::io::{stderr, Write};
+use std::time::Duration;
+
+/// Emulator controls triggered on key press
+#[derive(Clone, Copy, PartialEq)]
+pub enum Hotkey {
+    Quit,
+    Reset,
+    Pause,
+    FrameAdvance,
+    SlowMotion,
+    Fullscreen,
+    Screenshot,
+    Record,
+    Stats,
+    Debugger,
+    SpriteViewer,
+    Step,
+    VolumeUp,
+    VolumeDown,
+    Mute,
+    Waveform,
+    SaveState,
+    LoadState,
+}
+
+/// Left mouse button press in window coordinates
+#[derive(Clone, Copy)]
+pub struct Click {
+    pub window_id: u32,
+    pub x: i32,
+    pub y: i32,
+}
+
+/// Mouse wheel movement, positive `y` is away from the user
+#[derive(Clone, Copy)]
+pub struct Scroll {
+    pub window_id: u32,
+    pub y: i32,
+}
+
+pub struct InputState {
+    pub keys: [KeyState; KEY_SIZE],
+    pub hotkeys: Vec<Hotkey>,
+    pub fast_forward: bool, // Fast-forward is held down
+    pub redraw: bool,       // Window contents were lost or resized
+    pub clicks: Vec<Click>,
+    pub scrolls: Vec<Scroll>,
+    pub closed: Vec<u32>,       // IDs of windows the user closed
+    pub pointer: Option<Click>, // Left mouse button or finger held down
+}
+
+/// Emulator state shown by the debugger and sprite viewer
+pub struct DebugView<'a> {
+    pub regs: Registers,
+    pub memory: &'a [u8],
+    pub keys: &'a [KeyState; KEY_SIZE],
+    pub breakpoints: &'a BTreeSet<usize>,
+}
+
+/// Requests from tool windows that the emulator carries out
+pub enum ToolAction {
+    ToggleBreakpoint(usize),
+    ExportSprites(Vec<Sprite>),
+}
+
+/// Performance over the last second
+#[derive(Clone, Copy)]
+pub struct Stats {
+    pub ips: usize,                  // Instructions per second
+    pub fps: usize,                  // Display updates per second
+    pub frame_time: Duration,        // Wall time per emulated 60Hz frame
+    pub instructions_per_frame: f32, // Instructions per emulated 60Hz frame
+}
+
+/// Presents the emulated display
+pub trait Video {
+    /// Minimum time between draws
+    fn draw_interval(&self) -> Duration;
+
+    /// Draw pixel brightness values (see `Persistence`), `pixels` is `None`
+    /// when the display did not change since the last draw
+    fn draw(&mut self, pixels: Option<&[f32]>, regs: &Registers, stats: Option<Stats>);
+
+    /// Show a short status or error message to the user
+    fn show_message(&mut self, message: &str) {
+        writeln!(&mut stderr(), "{}", message).ok();
+    }
+
+    /// Show the keypad, `polled` has bit N set when the ROM tested key N
+    /// during the last frame
+    fn update_keypad(&mut self, _keys: &[KeyState; KEY_SIZE], _polled: u16) {}
+
+    /// On-screen keypad key under a held pointer
+    fn keypad_key(&self, _pointer: Click) -> Option<usize> {
+        None
+    }
+
+    fn toggle_fullscreen(&mut self) {}
+
+    fn toggle_stats(&mut self) {}
+
+    fn toggle_debugger(&mut self) {}
+
+    fn toggle_sprite_viewer(&mut self) {}
+
+    /// Update the debugger and sprite viewer if they are open
+    fn draw_tools(&mut self, _view: &DebugView) {}
+
+    fn click(&mut self, _click: Click) -> Option<ToolAction> {
+        None
+    }
+
+    fn scroll(&mut self, _scroll: Scroll) {}
+
+    /// Close a window, returns `false` if it was the main one
+    fn close_window(&mut self, _window_id: u32) -> bool {
+        false
+    }
+}
+
+/// Plays the buzzer
+pub trait Audio {
+    fn beep(&mut self, beep: bool);
+
+    fn set_tone(&mut self, _tone: Tone) {}
+
+    /// Emulated time advanced to `elapsed`, for sinks that generate samples
+    /// in emulated rather than real time
+    fn sync(&mut self, _elapsed: Duration) {}
+}
+
+impl<T: Audio + ?Sized> Audio for Box<T> {
+    fn beep(&mut self, beep: bool) {
+        (**self).beep(beep);
+    }
+
+    fn set_tone(&mut self, tone: Tone) {
+        (**self).set_tone(tone);
+    }
+
+    fn sync(&mut self, elapsed: Duration) {
+        (**self).sync(elapsed);
+    }
+}
+
+/// Reads the keypad and emulator controls, `Err` when the user quits
+pub trait Input {
+    fn poll(&mut self) -> Result<InputState, ()>;
+}
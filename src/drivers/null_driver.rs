@@ -0,0 +1,38 @@
+use super::{Audio, Input, InputState, Stats, Video};
+use crate::cpu::{KeyState, Registers, KEY_SIZE};
+use std::time::Duration;
+
+// Headless backends, nothing is presented and no keys are ever pressed
+
+pub struct NullVideo;
+
+impl Video for NullVideo {
+    fn draw_interval(&self) -> Duration {
+        Duration::from_secs(1)
+    }
+
+    fn draw(&mut self, _pixels: Option<&[f32]>, _regs: &Registers, _stats: Option<Stats>) {}
+}
+
+pub struct NullAudio;
+
+impl Audio for NullAudio {
+    fn beep(&mut self, _beep: bool) {}
+}
+
+pub struct NullInput;
+
+impl Input for NullInput {
+    fn poll(&mut self) -> Result<InputState, ()> {
+        Ok(InputState {
+            keys: [KeyState::NotPressed; KEY_SIZE],
+            hotkeys: Vec::new(),
+            fast_forward: false,
+            redraw: false,
+            clicks: Vec::new(),
+            scrolls: Vec::new(),
+            closed: Vec::new(),
+            pointer: None,
+        })
+    }
+}
@@ -0,0 +1,106 @@
+use super::text::{draw_text, text_width, GLYPH_SIZE};
+use super::Stats;
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::{BlendMode, Canvas};
+use sdl2::video::Window;
+use std::time::{Duration, Instant};
+
+// How long messages stay on screen
+const MESSAGE_DURATION: Duration = Duration::from_secs(2);
+
+// Font scale and spacing in output pixels
+const TEXT_SCALE: u32 = 2;
+const MARGIN: i32 = 8;
+const LINE_HEIGHT: i32 = (GLYPH_SIZE * TEXT_SCALE) as i32 + 4;
+
+/// On-screen display of transient messages and performance stats
+pub struct Osd {
+    messages: Vec<(String, Instant)>, // Message and when it expires
+    stats: Option<Stats>,
+    show_stats: bool,
+    dirty: bool, // Contents changed since the last render
+}
+
+impl Osd {
+    pub fn new(show_stats: bool) -> Self {
+        Self {
+            messages: Vec::new(),
+            stats: None,
+            show_stats,
+            dirty: false,
+        }
+    }
+
+    pub fn message(&mut self, message: &str) {
+        self.messages
+            .push((message.to_owned(), Instant::now() + MESSAGE_DURATION));
+        self.dirty = true;
+    }
+
+    pub fn set_stats(&mut self, stats: Stats) {
+        self.stats = Some(stats);
+        self.dirty |= self.show_stats;
+    }
+
+    pub fn toggle_stats(&mut self) {
+        self.show_stats = !self.show_stats;
+        self.dirty = true;
+    }
+
+    /// Drop expired messages, returns whether the OSD needs redrawing
+    pub fn update(&mut self) -> bool {
+        let now = Instant::now();
+        let count = self.messages.len();
+        self.messages.retain(|(_, expires)| *expires > now);
+        self.dirty |= self.messages.len() != count;
+        self.dirty
+    }
+
+    /// Draw stats in the top left and messages in the bottom left corner
+    pub fn render(&mut self, canvas: &mut Canvas<Window>) {
+        self.dirty = false;
+
+        let (_, out_h) = canvas.output_size().unwrap_or((0, 0));
+        let mut lines: Vec<(String, i32)> = Vec::new();
+
+        if let (true, Some(stats)) = (self.show_stats, self.stats) {
+            let stats_lines = [
+                format!("IPS {}", stats.ips),
+                format!("FPS {}", stats.fps),
+                format!("Frame {:.1} ms", stats.frame_time.as_secs_f64() * 1000.0),
+                format!("Instr/frame {:.1}", stats.instructions_per_frame),
+            ];
+            for (i, line) in stats_lines.iter().enumerate() {
+                lines.push((line.clone(), MARGIN + i as i32 * LINE_HEIGHT));
+            }
+        }
+
+        let bottom = out_h as i32 - MARGIN - LINE_HEIGHT;
+        for (i, (message, _)) in self.messages.iter().rev().enumerate() {
+            lines.push((message.clone(), bottom - i as i32 * LINE_HEIGHT));
+        }
+
+        // Text on a translucent backdrop so it reads on any palette
+        canvas.set_blend_mode(BlendMode::Blend);
+        for (text, y) in lines {
+            let width = text_width(&text, TEXT_SCALE);
+            canvas.set_draw_color(Color::RGBA(0, 0, 0, 160));
+            let _ = canvas.fill_rect(Rect::new(
+                MARGIN - 2,
+                y - 2,
+                width + 4,
+                (LINE_HEIGHT - 2) as u32,
+            ));
+            draw_text(
+                canvas,
+                MARGIN,
+                y,
+                TEXT_SCALE,
+                &text,
+                Color::RGB(255, 255, 255),
+            );
+        }
+        canvas.set_blend_mode(BlendMode::None);
+    }
+}
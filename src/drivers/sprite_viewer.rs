@@ -0,0 +1,280 @@
+use super::text::{draw_text, text_width, GLYPH_SIZE};
+use super::{Click, DebugView, ToolAction};
+use crate::palette::Palette;
+use crate::sprite::Sprite;
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::Canvas;
+use sdl2::video::Window;
+use std::time::{Duration, Instant};
+
+const TITLE: &str = "CHIP-8 Sprites";
+
+const DRAW_INTERVAL: Duration = Duration::from_millis(33);
+
+// Layout in output pixels
+const WINDOW_W: u32 = 640;
+const WINDOW_H: u32 = 560;
+const MARGIN: i32 = 8;
+const TEXT_SCALE: u32 = 2;
+const TOOLBAR_H: i32 = (GLYPH_SIZE * TEXT_SCALE) as i32 + 2 * MARGIN;
+const LABEL_W: i32 = 4 * GLYPH_SIZE as i32 + MARGIN; // Address in front of each row
+const PIXEL_SIZE: u32 = 4;
+const CELL_GAP: i32 = 6;
+
+// DXYN draws up to 15 rows, SCHIP's DXY0 a 16x16 sprite
+const MAX_HEIGHT: usize = 15;
+
+const TEXT: Color = Color::RGB(0xD0, 0xD0, 0xD0);
+const LABEL: Color = Color::RGB(0x80, 0x80, 0x80);
+const POINTED: Color = Color::RGB(0x40, 0x70, 0xE0); // Overlaps memory at I
+const SELECTED: Color = Color::RGB(0xF0, 0xC0, 0x20);
+
+#[derive(Clone, Copy)]
+enum Button {
+    Chip8,
+    Schip,
+    Shorter,
+    Taller,
+    Back,
+    Forward,
+    JumpToI,
+    Export,
+}
+
+/// Window showing memory as a grid of sprites. Clicking a sprite selects it
+/// for export, the wheel scrolls through memory.
+pub struct SpriteViewer {
+    canvas: Canvas<Window>,
+    palette: Palette,
+    start: usize,  // Address of the first sprite shown
+    schip: bool,   // 16x16 sprites instead of 8 pixels wide
+    height: usize, // Rows of 8 pixel wide sprites
+    selected: Vec<Sprite>,
+    buttons: Vec<(Rect, Button)>, // Toolbar as last drawn
+    cells: Vec<(Rect, Sprite)>,   // Grid as last drawn
+    columns: usize,
+    memory_size: usize,
+    pointer: usize, // I when last drawn
+    last_draw: Option<Instant>,
+}
+
+impl SpriteViewer {
+    pub fn new(video_subsystem: &sdl2::VideoSubsystem, palette: Palette) -> Result<Self, String> {
+        let window = video_subsystem
+            .window(TITLE, WINDOW_W, WINDOW_H)
+            .build()
+            .map_err(|e| e.to_string())?;
+        let canvas = window
+            .into_canvas()
+            .accelerated()
+            .build()
+            .map_err(|e| e.to_string())?;
+
+        Ok(Self {
+            canvas,
+            palette,
+            start: 0,
+            schip: false,
+            height: 8,
+            selected: Vec::new(),
+            buttons: Vec::new(),
+            cells: Vec::new(),
+            columns: 1,
+            memory_size: 0,
+            pointer: 0,
+            last_draw: None,
+        })
+    }
+
+    pub fn window_id(&self) -> u32 {
+        self.canvas.window().id()
+    }
+
+    fn sprite(&self, addr: usize) -> Sprite {
+        match self.schip {
+            true => Sprite {
+                addr,
+                width: 16,
+                height: 16,
+            },
+            false => Sprite {
+                addr,
+                width: 8,
+                height: self.height,
+            },
+        }
+    }
+
+    /// Move the first sprite shown by `offset` bytes, staying in memory
+    fn scroll_by(&mut self, offset: isize) {
+        let last = self.memory_size.saturating_sub(1);
+        self.start = self.start.saturating_add_signed(offset).min(last);
+        self.last_draw = None;
+    }
+
+    /// Scroll by rows of sprites, positive is towards the end of memory
+    pub fn scroll(&mut self, window_id: u32, rows: i32) {
+        if window_id == self.window_id() {
+            let row = (self.columns * self.sprite(0).len()) as isize;
+            self.scroll_by(-(rows as isize) * row);
+        }
+    }
+
+    pub fn click(&mut self, click: Click) -> Option<ToolAction> {
+        if click.window_id != self.window_id() {
+            return None;
+        }
+        let hit = |rect: &Rect| rect.contains_point((click.x, click.y));
+        self.last_draw = None;
+
+        if let Some(&(_, sprite)) = self.cells.iter().find(|(rect, _)| hit(rect)) {
+            match self.selected.iter().position(|s| *s == sprite) {
+                Some(index) => {
+                    self.selected.remove(index);
+                }
+                None => self.selected.push(sprite),
+            }
+            return None;
+        }
+
+        let (_, button) = *self.buttons.iter().find(|(rect, _)| hit(rect))?;
+        match button {
+            Button::Chip8 => self.schip = false,
+            Button::Schip => self.schip = true,
+            Button::Shorter => self.height = (self.height - 1).max(1),
+            Button::Taller => self.height = (self.height + 1).min(MAX_HEIGHT),
+            Button::Back => self.scroll_by(-1),
+            Button::Forward => self.scroll_by(1),
+            Button::JumpToI => self.start = self.pointer,
+            Button::Export if !self.selected.is_empty() => {
+                return Some(ToolAction::ExportSprites(self.selected.clone()));
+            }
+            Button::Export => (),
+        }
+        None
+    }
+
+    pub fn draw(&mut self, view: &DebugView) {
+        let now = Instant::now();
+        if self
+            .last_draw
+            .is_some_and(|last| now - last < DRAW_INTERVAL)
+        {
+            return;
+        }
+        self.last_draw = Some(now);
+        self.memory_size = view.memory.len();
+        self.pointer = view.regs.I;
+
+        self.canvas.set_draw_color(Color::RGB(0, 0, 0));
+        self.canvas.clear();
+        self.draw_toolbar();
+        self.draw_grid(view.memory);
+        self.canvas.present();
+    }
+
+    fn draw_toolbar(&mut self) {
+        let size = format!("8x{}", self.height);
+        let start = format!("{:03X}", self.start);
+        let items = [
+            (size.as_str(), Some(Button::Chip8)),
+            ("16x16", Some(Button::Schip)),
+            ("-", Some(Button::Shorter)),
+            ("+", Some(Button::Taller)),
+            ("<", Some(Button::Back)),
+            (start.as_str(), None),
+            (">", Some(Button::Forward)),
+            ("I", Some(Button::JumpToI)),
+            ("Export", Some(Button::Export)),
+        ];
+
+        self.buttons.clear();
+        let mut x = MARGIN;
+        for (label, button) in items.iter() {
+            let width = text_width(label, TEXT_SCALE);
+            let rect = Rect::new(
+                x - MARGIN / 2,
+                MARGIN / 2,
+                width + MARGIN as u32,
+                (TOOLBAR_H - MARGIN) as u32,
+            );
+            let active = match button {
+                Some(Button::Chip8) => !self.schip,
+                Some(Button::Schip) => self.schip,
+                _ => false,
+            };
+            if let Some(button) = button {
+                self.canvas
+                    .set_draw_color(if active { TEXT } else { LABEL });
+                let _ = self.canvas.draw_rect(rect);
+                self.buttons.push((rect, *button));
+            }
+            draw_text(&mut self.canvas, x, MARGIN, TEXT_SCALE, label, TEXT);
+            x += width as i32 + 2 * MARGIN;
+        }
+    }
+
+    fn draw_grid(&mut self, memory: &[u8]) {
+        let sprite = self.sprite(0);
+        let cell_w = sprite.width as i32 * PIXEL_SIZE as i32;
+        let cell_h = sprite.height as i32 * PIXEL_SIZE as i32;
+        self.columns =
+            ((WINDOW_W as i32 - LABEL_W - MARGIN + CELL_GAP) / (cell_w + CELL_GAP)).max(1) as usize;
+        let rows = ((WINDOW_H as i32 - TOOLBAR_H - MARGIN + CELL_GAP) / (cell_h + CELL_GAP)).max(1)
+            as usize;
+
+        let fg = self.palette.foreground;
+        let bg = self.palette.background;
+        self.cells.clear();
+        for row in 0..rows {
+            let y = TOOLBAR_H + row as i32 * (cell_h + CELL_GAP);
+            let row_addr = self.start + row * self.columns * sprite.len();
+            if row_addr >= memory.len() {
+                break;
+            }
+            let label = format!("{:03X}", row_addr);
+            draw_text(&mut self.canvas, MARGIN, y, 1, &label, LABEL);
+
+            for col in 0..self.columns {
+                let sprite = self.sprite(row_addr + col * sprite.len());
+                if sprite.addr >= memory.len() {
+                    break;
+                }
+                let x = LABEL_W + col as i32 * (cell_w + CELL_GAP);
+                let rect = Rect::new(x, y, cell_w as u32, cell_h as u32);
+                self.canvas.set_draw_color(Color::RGB(bg.r, bg.g, bg.b));
+                let _ = self.canvas.fill_rect(rect);
+
+                let mut lit = Vec::new();
+                for py in 0..sprite.height {
+                    for px in 0..sprite.width {
+                        if sprite.pixel(memory, px, py) {
+                            lit.push(Rect::new(
+                                x + (px as u32 * PIXEL_SIZE) as i32,
+                                y + (py as u32 * PIXEL_SIZE) as i32,
+                                PIXEL_SIZE,
+                                PIXEL_SIZE,
+                            ));
+                        }
+                    }
+                }
+                self.canvas.set_draw_color(Color::RGB(fg.r, fg.g, fg.b));
+                let _ = self.canvas.fill_rects(&lit);
+
+                // Outline sprites selected or overlapping the data at I
+                let border = Rect::new(x - 2, y - 2, cell_w as u32 + 4, cell_h as u32 + 4);
+                if self.selected.contains(&sprite) {
+                    self.canvas.set_draw_color(SELECTED);
+                    let _ = self.canvas.draw_rect(border);
+                } else if sprite.addr < self.pointer + sprite.len()
+                    && self.pointer < sprite.addr + sprite.len()
+                {
+                    self.canvas.set_draw_color(POINTED);
+                    let _ = self.canvas.draw_rect(border);
+                }
+                self.cells.push((rect, sprite));
+            }
+        }
+    }
+}
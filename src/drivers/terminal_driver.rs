@@ -0,0 +1,436 @@
+use super::{Audio, Input, InputState, Stats, Video};
+use crate::cpu::{KeyState, Registers, DISPLAY_H, DISPLAY_W, KEY_SIZE};
+use crate::hotkeys::{Action, HotkeyMap};
+use crate::keymap::KeyMap;
+use crate::palette::{Palette, Rgb};
+use crate::synth::Tone;
+use clap::arg_enum;
+use crossterm::event::{
+    self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, KeyboardEnhancementFlags,
+    PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+};
+use crossterm::style::{Color, Print, ResetColor, SetBackgroundColor, SetForegroundColor};
+use crossterm::{cursor, queue, terminal};
+use std::collections::HashMap;
+use std::io::{self, Stdout, Write};
+use std::time::{Duration, Instant};
+
+// Terminals that only report key presses repeat them while a key is held, so
+// keys count as held until no press was seen for this long
+const KEY_HOLD_DURATION: Duration = Duration::from_millis(150);
+
+// Terminal output is slow, limit its refresh rate to 30Hz
+const DRAW_INTERVAL: Duration = Duration::from_millis(33);
+
+// Columns between the display and the register panel
+const PANEL_GAP: u16 = 2;
+
+// How long messages stay in the panel
+const MESSAGE_DURATION: Duration = Duration::from_secs(2);
+
+arg_enum! {
+    #[derive(Clone, Copy, PartialEq)]
+    pub enum Glyphs {
+        HalfBlock,
+        Braille,
+    }
+}
+
+/// Puts the terminal into raw mode on the alternate screen until dropped
+pub struct TerminalSession {
+    release_events: bool, // Terminal reports key releases
+}
+
+/// Renders the display with Unicode characters next to a register panel
+pub struct TerminalVideo {
+    stdout: Stdout,
+    palette: Palette,
+    glyphs: Glyphs,
+    show_stats: bool,
+    stats: Option<Stats>,
+    message: Option<(String, Instant)>,
+}
+
+/// Reads the keypad from terminal key events
+pub struct TerminalInput {
+    release_events: bool,
+    keys: HashMap<char, usize>, // Lowercase host key to CHIP-8 key
+    hotkeys: HashMap<KeyCode, Action>,
+    pressed: [Option<Instant>; KEY_SIZE],
+    fast_forward: Option<Instant>,
+}
+
+/// Rings the terminal bell when the buzzer starts
+pub struct TerminalAudio {
+    beeping: bool,
+    muted: bool,
+}
+
+impl TerminalSession {
+    pub fn new() -> io::Result<Self> {
+        let mut stdout = io::stdout();
+        terminal::enable_raw_mode()?;
+        queue!(
+            stdout,
+            terminal::EnterAlternateScreen,
+            cursor::Hide,
+            terminal::Clear(terminal::ClearType::All)
+        )?;
+
+        let release_events = terminal::supports_keyboard_enhancement().unwrap_or(false);
+        if release_events {
+            queue!(
+                stdout,
+                PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::REPORT_EVENT_TYPES)
+            )?;
+        }
+        stdout.flush()?;
+
+        Ok(Self { release_events })
+    }
+}
+
+impl Drop for TerminalSession {
+    fn drop(&mut self) {
+        let mut stdout = io::stdout();
+        if self.release_events {
+            let _ = queue!(stdout, PopKeyboardEnhancementFlags);
+        }
+        let _ = queue!(
+            stdout,
+            ResetColor,
+            cursor::Show,
+            terminal::LeaveAlternateScreen
+        );
+        let _ = stdout.flush();
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+impl TerminalInput {
+    /// Only single character key names of `key_map` can be told apart by
+    /// terminals, others are ignored. Hotkeys can also use function keys and
+    /// a few named keys like "Escape".
+    pub fn new(session: &TerminalSession, key_map: &KeyMap, hotkey_map: &HotkeyMap) -> Self {
+        let mut keys = HashMap::new();
+        for (name, key) in key_map.bindings() {
+            if let Some(KeyCode::Char(c)) = key_code(name) {
+                keys.insert(c, key);
+            }
+        }
+        let hotkeys = hotkey_map
+            .bindings()
+            .filter_map(|(name, action)| Some((key_code(name)?, action)))
+            .collect();
+
+        Self {
+            release_events: session.release_events,
+            keys,
+            hotkeys,
+            pressed: [None; KEY_SIZE],
+            fast_forward: None,
+        }
+    }
+
+    /// Whether a key last pressed at `time` is still held down
+    fn held(&self, time: Option<Instant>, now: Instant) -> bool {
+        match time {
+            Some(time) => self.release_events || now - time <= KEY_HOLD_DURATION,
+            None => false,
+        }
+    }
+}
+
+impl Input for TerminalInput {
+    fn poll(&mut self) -> Result<InputState, ()> {
+        let now = Instant::now();
+        let mut hotkeys = Vec::new();
+        while event::poll(Duration::from_secs(0)).unwrap_or(false) {
+            let key = match event::read() {
+                Ok(Event::Key(key)) => key,
+                Ok(_) => continue,
+                Err(_) => return Err(()),
+            };
+
+            let held = match key.kind {
+                KeyEventKind::Release => None,
+                _ => Some(now),
+            };
+
+            // Keys mapped to the keypad are never hotkeys
+            let code = match key.code {
+                KeyCode::Char(c) => KeyCode::Char(lowercase(c)),
+                code => code,
+            };
+            let mapped = match code {
+                KeyCode::Char(c) => self.keys.get(&c).copied(),
+                _ => None,
+            };
+            match (mapped, self.hotkeys.get(&code)) {
+                (None, Some(Action::Press(hotkey))) => {
+                    if key.kind == KeyEventKind::Press {
+                        hotkeys.push(*hotkey);
+                    }
+                    continue;
+                }
+                (None, Some(Action::FastForward)) => {
+                    self.fast_forward = held;
+                    continue;
+                }
+                _ => (),
+            }
+
+            match key {
+                KeyEvent {
+                    code: KeyCode::Char('c'),
+                    modifiers: KeyModifiers::CONTROL,
+                    ..
+                } => return Err(()),
+                _ => {
+                    if let Some(idx) = mapped {
+                        self.pressed[idx] = held;
+                    }
+                }
+            }
+        }
+
+        let mut chip8_keys = [KeyState::NotPressed; KEY_SIZE];
+        for (key, &pressed) in chip8_keys.iter_mut().zip(self.pressed.iter()) {
+            if self.held(pressed, now) {
+                *key = KeyState::Pressed;
+            }
+        }
+
+        Ok(InputState {
+            keys: chip8_keys,
+            hotkeys,
+            fast_forward: self.held(self.fast_forward, now),
+            redraw: false,
+            clicks: Vec::new(),
+            scrolls: Vec::new(),
+            closed: Vec::new(),
+            pointer: None,
+        })
+    }
+}
+
+impl TerminalVideo {
+    pub fn new(palette: Palette, glyphs: Glyphs, show_stats: bool) -> Self {
+        Self {
+            stdout: io::stdout(),
+            palette,
+            glyphs,
+            show_stats,
+            stats: None,
+            message: None,
+        }
+    }
+
+    fn draw_display(&mut self, pixels: &[f32]) -> io::Result<()> {
+        let lit = |x: usize, y: usize| pixels[y * DISPLAY_W + x] >= 0.5;
+        match self.glyphs {
+            // Each character cell holds two pixels stacked vertically
+            Glyphs::HalfBlock => {
+                let (mut fg, mut bg) = (None, None);
+                for row in 0..DISPLAY_H / 2 {
+                    queue!(self.stdout, cursor::MoveTo(0, row as u16))?;
+                    for x in 0..DISPLAY_W {
+                        let top = self.palette.shade(pixels[2 * row * DISPLAY_W + x]);
+                        let bottom = self.palette.shade(pixels[(2 * row + 1) * DISPLAY_W + x]);
+
+                        // Only emit color changes to keep output small
+                        if fg != Some(top) {
+                            queue!(self.stdout, SetForegroundColor(color(top)))?;
+                            fg = Some(top);
+                        }
+                        if bg != Some(bottom) {
+                            queue!(self.stdout, SetBackgroundColor(color(bottom)))?;
+                            bg = Some(bottom);
+                        }
+                        queue!(self.stdout, Print('▀'))?;
+                    }
+                }
+            }
+
+            // Each character cell holds a 2x4 block of pixels
+            Glyphs::Braille => {
+                const DOTS: [[u32; 4]; 2] = [[0x01, 0x02, 0x04, 0x40], [0x08, 0x10, 0x20, 0x80]];
+                queue!(
+                    self.stdout,
+                    SetForegroundColor(color(self.palette.foreground)),
+                    SetBackgroundColor(color(self.palette.background))
+                )?;
+                for row in 0..DISPLAY_H / 4 {
+                    queue!(self.stdout, cursor::MoveTo(0, row as u16))?;
+                    for col in 0..DISPLAY_W / 2 {
+                        let mut bits = 0;
+                        for (dx, dots) in DOTS.iter().enumerate() {
+                            for (dy, dot) in dots.iter().enumerate() {
+                                if lit(2 * col + dx, 4 * row + dy) {
+                                    bits |= dot;
+                                }
+                            }
+                        }
+                        let c = std::char::from_u32(0x2800 + bits).unwrap_or(' ');
+                        queue!(self.stdout, Print(c))?;
+                    }
+                }
+            }
+        }
+        queue!(self.stdout, ResetColor)
+    }
+
+    fn draw_panel(&mut self, regs: &Registers) -> io::Result<()> {
+        let left = match self.glyphs {
+            Glyphs::HalfBlock => DISPLAY_W,
+            Glyphs::Braille => DISPLAY_W / 2,
+        } as u16
+            + PANEL_GAP;
+
+        let mut lines = vec![
+            format!("PC {:03X}  I  {:03X}", regs.PC, regs.I),
+            format!("DT {:02X}   ST {:02X}", regs.delay_timer, regs.sound_timer),
+            String::new(),
+        ];
+        for (row, values) in regs.V.chunks(4).enumerate() {
+            let line: Vec<_> = values
+                .iter()
+                .enumerate()
+                .map(|(i, v)| format!("V{:X} {:02X}", row * 4 + i, v))
+                .collect();
+            lines.push(line.join(" "));
+        }
+        lines.push(String::new());
+        let stack: Vec<_> = regs.stack[1..=regs.SP]
+            .iter()
+            .map(|addr| format!("{:03X}", addr))
+            .collect();
+        lines.push(format!("SP {:X}  {}", regs.SP, stack.join(" ")));
+        // Fixed number of lines so shorter contents overwrite longer ones
+        lines.push(String::new());
+        match self.stats {
+            Some(stats) if self.show_stats => {
+                lines.push(format!("IPS {}  FPS {}", stats.ips, stats.fps));
+                lines.push(format!(
+                    "Frame {:.1}ms  {:.1} IPF",
+                    stats.frame_time.as_secs_f64() * 1000.0,
+                    stats.instructions_per_frame
+                ));
+            }
+            Some(stats) => lines.extend([format!("IPS {}", stats.ips), String::new()]),
+            None => lines.extend([String::new(), String::new()]),
+        }
+        lines.push(String::new());
+        if self
+            .message
+            .as_ref()
+            .is_some_and(|(_, shown)| shown.elapsed() >= MESSAGE_DURATION)
+        {
+            self.message = None;
+        }
+        lines.push(
+            self.message
+                .as_ref()
+                .map_or(String::new(), |(m, _)| m.clone()),
+        );
+
+        for (row, line) in lines.iter().enumerate() {
+            queue!(
+                self.stdout,
+                cursor::MoveTo(left, row as u16),
+                terminal::Clear(terminal::ClearType::UntilNewLine),
+                Print(line)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl Video for TerminalVideo {
+    fn draw_interval(&self) -> Duration {
+        DRAW_INTERVAL
+    }
+
+    /// Registers are redrawn every time, the display only when it changed
+    fn draw(&mut self, pixels: Option<&[f32]>, regs: &Registers, stats: Option<Stats>) {
+        if stats.is_some() {
+            self.stats = stats;
+        }
+        if let Some(pixels) = pixels {
+            let _ = self.draw_display(pixels);
+        }
+        let _ = self.draw_panel(regs);
+        let _ = self.stdout.flush();
+    }
+
+    fn show_message(&mut self, message: &str) {
+        self.message = Some((message.to_string(), Instant::now()));
+    }
+
+    fn toggle_stats(&mut self) {
+        self.show_stats = !self.show_stats;
+    }
+}
+
+impl TerminalAudio {
+    pub fn new() -> Self {
+        Self {
+            beeping: false,
+            muted: false,
+        }
+    }
+}
+
+impl Audio for TerminalAudio {
+    fn beep(&mut self, beep: bool) {
+        if beep && !self.beeping && !self.muted {
+            let mut stdout = io::stdout();
+            let _ = stdout.write_all(b"\x07");
+            let _ = stdout.flush();
+        }
+        self.beeping = beep;
+    }
+
+    fn set_tone(&mut self, tone: Tone) {
+        self.muted = tone.muted;
+    }
+}
+
+/// Terminal key for an SDL key name, characters in lowercase
+fn key_code(name: &str) -> Option<KeyCode> {
+    let mut chars = name.chars().flat_map(char::to_lowercase);
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        return Some(KeyCode::Char(c));
+    }
+    let code = match name.to_ascii_lowercase().as_str() {
+        "escape" => KeyCode::Esc,
+        "tab" => KeyCode::Tab,
+        "space" => KeyCode::Char(' '),
+        "return" => KeyCode::Enter,
+        "backspace" => KeyCode::Backspace,
+        "delete" => KeyCode::Delete,
+        "insert" => KeyCode::Insert,
+        "home" => KeyCode::Home,
+        "end" => KeyCode::End,
+        "pageup" => KeyCode::PageUp,
+        "pagedown" => KeyCode::PageDown,
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        name => KeyCode::F(name.strip_prefix('f')?.parse().ok()?),
+    };
+    Some(code)
+}
+
+fn lowercase(c: char) -> char {
+    c.to_lowercase().next().unwrap_or(c)
+}
+
+fn color(rgb: Rgb) -> Color {
+    Color::Rgb {
+        r: rgb.r,
+        g: rgb.g,
+        b: rgb.b,
+    }
+}
@@ -0,0 +1,48 @@
+use font8x8::legacy::BASIC_LEGACY;
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::Canvas;
+use sdl2::video::Window;
+
+// Glyphs are 8x8 pixels before scaling
+pub const GLYPH_SIZE: u32 = 8;
+
+/// Draw ASCII text with its top left corner at (x, y), each font pixel
+/// enlarged to `scale` by `scale`
+pub fn draw_text(
+    canvas: &mut Canvas<Window>,
+    x: i32,
+    y: i32,
+    scale: u32,
+    text: &str,
+    color: Color,
+) {
+    let mut rects = Vec::new();
+    for (col, c) in text.chars().enumerate() {
+        let glyph = BASIC_LEGACY
+            .get(c as usize)
+            .unwrap_or(&BASIC_LEGACY[b'?' as usize]);
+        let left = x + (col as u32 * GLYPH_SIZE * scale) as i32;
+        for (row, bits) in glyph.iter().enumerate() {
+            for bit in 0..GLYPH_SIZE {
+                // Least significant bit is the leftmost pixel
+                if bits & (1 << bit) != 0 {
+                    rects.push(Rect::new(
+                        left + (bit * scale) as i32,
+                        y + (row as u32 * scale) as i32,
+                        scale,
+                        scale,
+                    ));
+                }
+            }
+        }
+    }
+
+    canvas.set_draw_color(color);
+    let _ = canvas.fill_rects(&rects);
+}
+
+/// Width of `text` in output pixels
+pub fn text_width(text: &str, scale: u32) -> u32 {
+    text.chars().count() as u32 * GLYPH_SIZE * scale
+}
@@ -0,0 +1,259 @@
+use crate::drivers::Hotkey;
+use crate::keymap::KeyMap;
+use std::path::Path;
+
+/// Emulator control bound to a host key
+#[derive(Clone, Copy, PartialEq)]
+pub enum Action {
+    Press(Hotkey), // Triggered when the key goes down
+    FastForward,   // Active while the key is held
+}
+
+// Action names used in hotkey files
+const ACTIONS: &[(&str, Action)] = &[
+    ("quit", Action::Press(Hotkey::Quit)),
+    ("reset", Action::Press(Hotkey::Reset)),
+    ("pause", Action::Press(Hotkey::Pause)),
+    ("frame-advance", Action::Press(Hotkey::FrameAdvance)),
+    ("step", Action::Press(Hotkey::Step)),
+    ("slow-motion", Action::Press(Hotkey::SlowMotion)),
+    ("fast-forward", Action::FastForward),
+    ("save-state", Action::Press(Hotkey::SaveState)),
+    ("load-state", Action::Press(Hotkey::LoadState)),
+    ("screenshot", Action::Press(Hotkey::Screenshot)),
+    ("record", Action::Press(Hotkey::Record)),
+    ("fullscreen", Action::Press(Hotkey::Fullscreen)),
+    ("stats", Action::Press(Hotkey::Stats)),
+    ("debugger", Action::Press(Hotkey::Debugger)),
+    ("sprite-viewer", Action::Press(Hotkey::SpriteViewer)),
+    ("volume-up", Action::Press(Hotkey::VolumeUp)),
+    ("volume-down", Action::Press(Hotkey::VolumeDown)),
+    ("mute", Action::Press(Hotkey::Mute)),
+    ("waveform", Action::Press(Hotkey::Waveform)),
+];
+
+// Built-in table in the hotkey file format
+const DEFAULT: &str = "
+quit = Escape
+reset = F2
+pause = P
+frame-advance = N
+step = F7
+slow-motion = `
+fast-forward = Tab
+save-state = F5
+load-state = F10
+screenshot = F12
+record = F9
+fullscreen = F11
+stats = F3
+debugger = F8
+sprite-viewer = F6
+volume-up = =
+volume-down = -
+mute = M
+waveform = F4
+";
+
+/// Host keys bound to emulator controls, by SDL key name. An action can have
+/// any number of keys, a key only one action.
+///
+/// Files have one binding per line, `<action> = <host key>`, e.g.
+/// `save-state = F1`. Actions listed in a file lose their built-in keys, `none`
+/// leaves an action unbound. Lines starting with `#` are comments.
+#[derive(Clone)]
+pub struct HotkeyMap {
+    bindings: Vec<(String, Action)>,
+}
+
+impl HotkeyMap {
+    pub fn builtin() -> Self {
+        Self::parse(DEFAULT).expect("built-in hotkeys")
+    }
+
+    /// Built-in table with the actions in the file at `path` rebound
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        let file = Self::parse(&text)?;
+
+        let mut bindings = Self::builtin().bindings;
+        bindings.retain(|(_, action)| !text_binds(&text, *action));
+        bindings.extend(file.bindings);
+        check_duplicates(&bindings)?;
+        Ok(Self { bindings })
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut bindings = Vec::new();
+        for (number, line) in lines(text) {
+            let error = |message: &str| format!("line {}: {}", number + 1, message);
+            let (action, host) = line
+                .split_once('=')
+                .ok_or_else(|| error("expected <action> = <host key>"))?;
+            let action = action_named(action.trim()).ok_or_else(|| error("unknown action"))?;
+            let host = host.trim();
+            if host.is_empty() {
+                return Err(error("missing host key"));
+            }
+            if !host.eq_ignore_ascii_case("none") {
+                bindings.push((host.to_string(), action));
+            }
+        }
+        check_duplicates(&bindings)?;
+        Ok(Self { bindings })
+    }
+
+    /// Host key names with the action each one triggers
+    pub fn bindings(&self) -> impl Iterator<Item = (&str, Action)> {
+        self.bindings
+            .iter()
+            .map(|(host, action)| (host.as_str(), *action))
+    }
+
+    /// Hotkeys whose host key also presses a keypad key, the keypad wins
+    pub fn conflicts(&self, key_map: &KeyMap) -> Vec<String> {
+        let mut conflicts = Vec::new();
+        for (host, action) in self.bindings() {
+            for (keypad_host, key) in key_map.bindings() {
+                if host.eq_ignore_ascii_case(keypad_host) {
+                    conflicts.push(format!(
+                        "{} ({}) is keypad key {:X}",
+                        action_name(action),
+                        host,
+                        key
+                    ));
+                }
+            }
+        }
+        conflicts
+    }
+}
+
+fn lines(text: &str) -> impl Iterator<Item = (usize, &str)> {
+    text.lines()
+        .map(str::trim)
+        .enumerate()
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+}
+
+/// Whether a hotkey file rebinds `action`
+fn text_binds(text: &str, action: Action) -> bool {
+    lines(text).any(|(_, line)| {
+        line.split_once('=')
+            .and_then(|(name, _)| action_named(name.trim()))
+            == Some(action)
+    })
+}
+
+fn check_duplicates(bindings: &[(String, Action)]) -> Result<(), String> {
+    for (index, (host, action)) in bindings.iter().enumerate() {
+        let other = bindings[..index]
+            .iter()
+            .find(|(other, _)| other.eq_ignore_ascii_case(host));
+        if let Some((_, other)) = other {
+            return Err(format!(
+                "{} is bound to both {} and {}",
+                host,
+                action_name(*other),
+                action_name(*action)
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn action_named(name: &str) -> Option<Action> {
+    ACTIONS
+        .iter()
+        .find(|(action, _)| action.eq_ignore_ascii_case(name))
+        .map(|&(_, action)| action)
+}
+
+fn action_name(action: Action) -> &'static str {
+    ACTIONS
+        .iter()
+        .find(|&&(_, other)| other == action)
+        .map_or("?", |&(name, _)| name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keymap::KeyMapping;
+
+    fn action(map: &HotkeyMap, host: &str) -> Option<Action> {
+        map.bindings()
+            .find(|(other, _)| other.eq_ignore_ascii_case(host))
+            .map(|(_, action)| action)
+    }
+
+    fn load(name: &str, text: &str) -> Result<HotkeyMap, String> {
+        let path = std::env::temp_dir().join(format!("chip8_emu_{}_{}", std::process::id(), name));
+        std::fs::write(&path, text).unwrap();
+        let map = HotkeyMap::load(&path);
+        std::fs::remove_file(&path).ok();
+        map
+    }
+
+    #[test]
+    fn builtin_binds_every_action() {
+        let map = HotkeyMap::builtin();
+        for &(_, action) in ACTIONS {
+            assert!(map.bindings().any(|(_, bound)| bound == action));
+        }
+        assert!(action(&map, "Escape") == Some(Action::Press(Hotkey::Quit)));
+        assert!(action(&map, "Tab") == Some(Action::FastForward));
+    }
+
+    #[test]
+    fn load_rebinds_listed_actions() {
+        let map = load(
+            "rebind",
+            "# Emulator keys\nsave-state = F1\nsave-state = S\nquit = none\n",
+        )
+        .unwrap();
+        assert!(action(&map, "F1") == Some(Action::Press(Hotkey::SaveState)));
+        assert!(action(&map, "S") == Some(Action::Press(Hotkey::SaveState)));
+        assert!(action(&map, "F5").is_none());
+        assert!(action(&map, "Escape").is_none());
+        assert!(action(&map, "F10") == Some(Action::Press(Hotkey::LoadState)));
+    }
+
+    #[test]
+    fn duplicate_keys_are_rejected() {
+        assert_eq!(
+            HotkeyMap::parse("reset = F2\npause = f2").err().unwrap(),
+            "f2 is bound to both reset and pause"
+        );
+        // Including against the built-in keys a file leaves in place
+        assert_eq!(
+            load("duplicate", "pause = Escape").err().unwrap(),
+            "Escape is bound to both quit and pause"
+        );
+    }
+
+    #[test]
+    fn parse_errors_name_the_line() {
+        assert_eq!(
+            HotkeyMap::parse("\nrewind = R").err().unwrap(),
+            "line 2: unknown action"
+        );
+        assert_eq!(
+            HotkeyMap::parse("pause").err().unwrap(),
+            "line 1: expected <action> = <host key>"
+        );
+        assert_eq!(
+            HotkeyMap::parse("pause =").err().unwrap(),
+            "line 1: missing host key"
+        );
+    }
+
+    #[test]
+    fn conflicts_with_keypad() {
+        let conflicts = HotkeyMap::builtin().conflicts(&KeyMap::builtin(KeyMapping::Dvorak));
+        assert_eq!(conflicts, ["pause (P) is keypad key D"]);
+        assert!(HotkeyMap::builtin()
+            .conflicts(&KeyMap::builtin(KeyMapping::QWERTY))
+            .is_empty());
+    }
+}
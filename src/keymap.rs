@@ -0,0 +1,279 @@
+use crate::cpu::KEY_SIZE;
+use clap::arg_enum;
+use std::path::Path;
+
+arg_enum! {
+    #[derive(Clone, Copy, PartialEq)]
+    pub enum KeyMapping {
+        Literal,
+        QWERTY,
+        AZERTY,
+        Dvorak,
+        Numpad,
+    }
+}
+
+// Built-in maps in the key map file format. The keyboard layouts use the 4x4
+// block under 1-4, laid out like the COSMAC VIP keypad:
+//   1 2 3 C
+//   4 5 6 D
+//   7 8 9 E
+//   A 0 B F
+
+const LITERAL: &str = "
+0 = 0
+0 = Keypad 0
+1 = 1
+1 = Keypad 1
+2 = 2
+2 = Keypad 2
+3 = 3
+3 = Keypad 3
+4 = 4
+4 = Keypad 4
+5 = 5
+5 = Keypad 5
+6 = 6
+6 = Keypad 6
+7 = 7
+7 = Keypad 7
+8 = 8
+8 = Keypad 8
+9 = 9
+9 = Keypad 9
+A = A
+B = B
+C = C
+D = D
+E = E
+F = F
+";
+
+const QWERTY: &str = "
+1 = 1
+2 = 2
+3 = 3
+C = 4
+4 = Q
+5 = W
+6 = E
+D = R
+7 = A
+8 = S
+9 = D
+E = F
+A = Z
+0 = X
+B = C
+F = V
+";
+
+const AZERTY: &str = "
+1 = &
+2 = é
+3 = \"
+C = '
+4 = A
+5 = Z
+6 = E
+D = R
+7 = Q
+8 = S
+9 = D
+E = F
+A = W
+0 = X
+B = C
+F = V
+";
+
+const DVORAK: &str = "
+1 = 1
+2 = 2
+3 = 3
+C = 4
+4 = '
+5 = ,
+6 = .
+D = P
+7 = A
+8 = O
+9 = E
+E = U
+A = ;
+0 = Q
+B = J
+F = K
+";
+
+// Keypad digits in their positions, the operators on the right column
+const NUMPAD: &str = "
+1 = Keypad 7
+2 = Keypad 8
+3 = Keypad 9
+C = Keypad /
+4 = Keypad 4
+5 = Keypad 5
+6 = Keypad 6
+D = Keypad *
+7 = Keypad 1
+8 = Keypad 2
+9 = Keypad 3
+E = Keypad -
+A = Keypad Enter
+0 = Keypad 0
+B = Keypad .
+F = Keypad +
+";
+
+// Game controllers, directions on 2/4/6/8 and 5 as the action button like
+// most games use them
+const GAMEPAD: &str = "
+2 = dpup
+8 = dpdown
+4 = dpleft
+6 = dpright
+2 = lefty-
+8 = lefty+
+4 = leftx-
+6 = leftx+
+5 = a
+0 = b
+7 = x
+9 = y
+1 = leftshoulder
+3 = rightshoulder
+";
+
+/// Host keys bound to CHIP-8 keys, by SDL key name (e.g. "Q", "Keypad 7").
+/// A CHIP-8 key can have any number of host keys.
+///
+/// Files have one binding per line, `<CHIP-8 key> = <host key>`, e.g.
+/// `A = Z`. Lines starting with `#` are comments.
+#[derive(Clone)]
+pub struct KeyMap {
+    bindings: Vec<(String, usize)>,
+}
+
+impl KeyMap {
+    pub fn builtin(mapping: KeyMapping) -> Self {
+        let text = match mapping {
+            KeyMapping::Literal => LITERAL,
+            KeyMapping::QWERTY => QWERTY,
+            KeyMapping::AZERTY => AZERTY,
+            KeyMapping::Dvorak => DVORAK,
+            KeyMapping::Numpad => NUMPAD,
+        };
+        Self::parse(text).expect("built-in key map")
+    }
+
+    /// Default game controller map, by SDL controller button and axis name
+    pub fn gamepad() -> Self {
+        Self::parse(GAMEPAD).expect("built-in gamepad map")
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        Self::parse(&text)
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut bindings = Vec::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let error = |message: &str| format!("line {}: {}", number + 1, message);
+            let (key, host) = line
+                .split_once('=')
+                .ok_or_else(|| error("expected <CHIP-8 key> = <host key>"))?;
+            let key = match usize::from_str_radix(key.trim(), 16) {
+                Ok(key) if key < KEY_SIZE => key,
+                _ => return Err(error("CHIP-8 key must be 0-F")),
+            };
+            let host = host.trim();
+            if host.is_empty() {
+                return Err(error("missing host key"));
+            }
+            bindings.push((host.to_string(), key));
+        }
+        Ok(Self { bindings })
+    }
+
+    /// Bind a host key, replacing what it was bound to
+    pub fn bind(&mut self, host: &str, key: usize) {
+        self.bindings
+            .retain(|(other, _)| !other.eq_ignore_ascii_case(host));
+        self.bindings.push((host.to_string(), key));
+    }
+
+    /// Host key names with the CHIP-8 key each one presses
+    pub fn bindings(&self) -> impl Iterator<Item = (&str, usize)> {
+        self.bindings
+            .iter()
+            .map(|(host, key)| (host.as_str(), *key))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bindings(map: &KeyMap) -> Vec<(&str, usize)> {
+        map.bindings().collect()
+    }
+
+    #[test]
+    fn parse_skips_comments_and_blank_lines() {
+        let map = KeyMap::parse("# Arrows\n\n8 = Up\n 2 = Down \nA = Keypad Enter\n").unwrap();
+        assert_eq!(
+            bindings(&map),
+            [("Up", 8), ("Down", 2), ("Keypad Enter", 0xA)]
+        );
+    }
+
+    #[test]
+    fn parse_errors_name_the_line() {
+        assert_eq!(
+            KeyMap::parse("1 = Q\nQ").err().unwrap(),
+            "line 2: expected <CHIP-8 key> = <host key>"
+        );
+        assert_eq!(
+            KeyMap::parse("10 = Q").err().unwrap(),
+            "line 1: CHIP-8 key must be 0-F"
+        );
+        assert_eq!(
+            KeyMap::parse("G = Q").err().unwrap(),
+            "line 1: CHIP-8 key must be 0-F"
+        );
+        assert_eq!(
+            KeyMap::parse("1 = ").err().unwrap(),
+            "line 1: missing host key"
+        );
+    }
+
+    #[test]
+    fn builtin_maps_cover_every_key() {
+        for mapping in &[
+            KeyMapping::Literal,
+            KeyMapping::QWERTY,
+            KeyMapping::AZERTY,
+            KeyMapping::Dvorak,
+            KeyMapping::Numpad,
+        ] {
+            let map = KeyMap::builtin(*mapping);
+            for key in 0..KEY_SIZE {
+                assert!(map.bindings().any(|(_, bound)| bound == key));
+            }
+        }
+    }
+
+    #[test]
+    fn bind_replaces_host_key() {
+        let mut map = KeyMap::parse("1 = Q\n2 = w\n2 = E").unwrap();
+        map.bind("W", 5);
+        assert_eq!(bindings(&map), [("Q", 1), ("E", 2), ("W", 5)]);
+    }
+}
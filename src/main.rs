@@ -1,33 +1,52 @@
-use crate::cpu::{CPUState, CycleInput, CPU};
-use drivers::{AudioDriver, DisplayDriver, InputDriver};
-use std::io::{stderr, Write};
+use crate::config::{Config, Frontend};
+use crate::cpu::{CPUState, CycleInput, KeyState, CPU, DISPLAY_H, DISPLAY_W, KEY_SIZE};
+use crate::movie::{Movie, MovieHeader};
+use crate::persistence::Persistence;
+use crate::recorder::{Format, Recorder};
+use crate::sprite::Sprite;
+use crate::synth::Tone;
+use crate::wav::WavRecorder;
+use drivers::{
+    Audio, AudioDriver, DebugView, DisplayDriver, FileAudio, Hotkey, Input, InputDriver, NullAudio,
+    NullInput, NullVideo, Stats, TerminalAudio, TerminalInput, TerminalSession, TerminalVideo,
+    ToolAction, Video,
+};
+use std::collections::BTreeSet;
+use std::fs::File;
+use std::io::{stderr, BufWriter, Write};
+use std::path::{Path, PathBuf};
 
 mod config;
 mod cpu;
+mod database;
+mod disasm;
 mod drivers;
-
-// 60Hz Chip-8 timers
-const CH8_TIMER_DURATION: std::time::Duration = std::time::Duration::from_nanos(16_666_666);
-
-// Limit window refresh rate to 100Hz
-const DRAW_TIMER_DURATION: std::time::Duration = std::time::Duration::from_millis(10);
+mod hotkeys;
+mod keymap;
+mod movie;
+mod palette;
+mod persistence;
+mod quirks;
+mod recorder;
+mod screenshot;
+mod settings;
+mod sprite;
+mod synth;
+mod wav;
 
 // Performance monitoring timers
 const PERF_TIMER_DURATION: std::time::Duration = std::time::Duration::from_secs(1);
 
+// Volume change per key press
+const VOLUME_STEP: f32 = 0.1;
+
+// Input polling interval while paused
+const PAUSE_SLEEP_DURATION: std::time::Duration = std::time::Duration::from_millis(10);
+
 fn main() {
     // Read configuration from command line
     let config = config::get_config();
 
-    // set tick rate
-    let target_sleep_duration = std::time::Duration::from_nanos(match config.rate {
-        Some(rate) => {
-            let secs_per_instr: f64 = 1.0 / rate as f64;
-            (secs_per_instr * 1_000_000_000f64) as u64
-        }
-        None => 0,
-    });
-
     // Load ROM file
     let rom = match std::fs::read(&config.rom_file) {
         Ok(v) => v,
@@ -37,69 +56,606 @@ fn main() {
         }
     };
 
+    // Timers run off emulated time even when the tick rate is unlimited
+    let mut rate = config.rate.unwrap_or(config::DEFAULT_RATE);
+    let mut seed = config.seed.unwrap_or_else(rand::random);
+
+    // Movies replay with the seed and rate they were recorded with
+    let mut movie = None;
+    if let Some(file) = &config.movie_play {
+        match Movie::play(Path::new(file)) {
+            Ok((playback, header)) => {
+                if header.rom_hash != MovieHeader::new(&rom, seed, rate).rom_hash {
+                    writeln!(&mut stderr(), "{:?}: recorded with a different ROM", file).ok();
+                    std::process::exit(1);
+                }
+                rate = header.rate;
+                seed = header.seed;
+                movie = Some(playback);
+            }
+            Err(e) => {
+                writeln!(&mut stderr(), "{:?}: {}", file, e).ok();
+                std::process::exit(1);
+            }
+        }
+    }
+    if let Some(file) = &config.movie_record {
+        match Movie::record(Path::new(file), &MovieHeader::new(&rom, seed, rate)) {
+            Ok(recording) => movie = Some(recording),
+            Err(e) => {
+                writeln!(&mut stderr(), "{:?}: {}", file, e).ok();
+                std::process::exit(1);
+            }
+        }
+    }
+
+    // set tick rate, headless runs go as fast as possible
+    let target_sleep_duration = std::time::Duration::from_nanos(match config.rate {
+        _ if config.frontend == Frontend::Null => 0,
+        Some(_) => {
+            let secs_per_instr: f64 = 1.0 / rate as f64;
+            (secs_per_instr * 1_000_000_000f64) as u64
+        }
+        None => 0,
+    });
+
     // Initialize emulated CPU
-    let mut cpu = CPU::new(&rom[..]);
+    let mut cpu = CPU::new(&rom[..], rate, seed, config.quirks);
 
-    // Initialize drivers
-    let sdl_context = sdl2::init().unwrap();
-    let mut display_driver = DisplayDriver::new(&sdl_context);
-    let mut input_driver = InputDriver::new(&sdl_context);
-    let audio_driver = AudioDriver::new(&sdl_context);
+    // Initialize drivers and run
+    match config.frontend {
+        Frontend::SDL => {
+            let sdl_context = sdl2::init().unwrap();
+            let display_driver = DisplayDriver::new(
+                &sdl_context,
+                config.palette,
+                config.scale,
+                config.integer_scaling,
+                config.osd_stats,
+                config.virtual_keypad,
+            );
+            let input_driver = InputDriver::new(
+                &sdl_context,
+                &config.key_map,
+                &config.hotkeys,
+                &config.pad_map,
+                config.stick_threshold,
+            );
+            let audio_driver = audio_override(&config).unwrap_or_else(|| {
+                match AudioDriver::new(&sdl_context, config.tone) {
+                    Ok(driver) => Box::new(driver),
+                    Err(e) => {
+                        writeln!(&mut stderr(), "Audio: {}, continuing without sound", e).ok();
+                        Box::new(NullAudio)
+                    }
+                }
+            });
+            run(
+                &config,
+                &mut cpu,
+                display_driver,
+                audio_driver,
+                input_driver,
+                target_sleep_duration,
+                movie,
+            );
+        }
+        Frontend::Terminal => {
+            let session = match TerminalSession::new() {
+                Ok(session) => session,
+                Err(e) => {
+                    writeln!(&mut stderr(), "Terminal: {}", e).ok();
+                    std::process::exit(1);
+                }
+            };
+            run(
+                &config,
+                &mut cpu,
+                TerminalVideo::new(config.palette, config.glyphs, config.osd_stats),
+                audio_override(&config).unwrap_or_else(|| Box::new(TerminalAudio::new())),
+                TerminalInput::new(&session, &config.key_map, &config.hotkeys),
+                target_sleep_duration,
+                movie,
+            );
+        }
+        Frontend::Null => run(
+            &config,
+            &mut cpu,
+            NullVideo,
+            audio_override(&config).unwrap_or_else(|| Box::new(NullAudio)),
+            NullInput,
+            target_sleep_duration,
+            movie,
+        ),
+    }
 
+    // Headless screenshot once the requested frame is reached
+    if let Some(frame) = config.screenshot_at {
+        let path = match &config.screenshot_file {
+            Some(file) => PathBuf::from(file),
+            None => output_path(&config, frame, "png"),
+        };
+        let mut persistence = Persistence::new(0, DISPLAY_W * DISPLAY_H);
+        let pixels = persistence.apply(cpu.gfx(), 0);
+        if !save_screenshot(&config, &path, pixels, &mut NullVideo) {
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Audio sink chosen on the command line, `None` for the frontend's own
+fn audio_override(config: &Config) -> Option<Box<dyn Audio>> {
+    if config.no_audio {
+        return Some(Box::new(NullAudio));
+    }
+
+    let file = config.audio_dump.as_ref()?;
+    let writer: Box<dyn Write> = if file == "-" {
+        Box::new(std::io::stdout())
+    } else {
+        match File::create(file) {
+            Ok(file) => Box::new(BufWriter::new(file)),
+            Err(e) => {
+                writeln!(&mut stderr(), "{:?}: {}", file, e).ok();
+                std::process::exit(1);
+            }
+        }
+    };
+    Some(Box::new(FileAudio::new(writer, config.tone)))
+}
+
+/// Emulation loop, runs until the user quits or the frame limit is reached
+fn run<V: Video, A: Audio, I: Input>(
+    config: &Config,
+    cpu: &mut CPU,
+    mut video: V,
+    mut audio: A,
+    mut input: I,
+    target_sleep_duration: std::time::Duration,
+    mut movie: Option<Movie>,
+) {
     // Initialize periodic timers
-    let ch8_ticker = crossbeam::channel::tick(CH8_TIMER_DURATION);
     let perf_ticker = crossbeam::channel::tick(PERF_TIMER_DURATION);
-    let draw_ticker = crossbeam::channel::tick(DRAW_TIMER_DURATION);
+    let draw_ticker = crossbeam::channel::tick(video.draw_interval());
+
+    // Buzzer sound, adjustable at runtime
+    let mut tone = config.tone;
+    audio.set_tone(tone);
+
+    // Gameplay recording
+    let mut recorder = config.record.as_ref().and_then(|(file, format)| {
+        start_recording(config, Path::new(file), *format, tone, &mut video)
+    });
+    let mut frame_beep = false;
+
+    // Keys tested by the ROM, shown on the on-screen keypad
+    let mut frame_polled: u16 = 0;
+    let mut polled: u16 = 0;
+
+    // Audio recording, sample accurate in emulated time
+    let mut wav = config.record_wav.as_ref().and_then(|file| {
+        match WavRecorder::create(Path::new(file), tone) {
+            Ok(wav) => Some(wav),
+            Err(e) => {
+                video.show_message(&format!("{:?}: {}", file, e));
+                None
+            }
+        }
+    });
+
+    // Phosphor persistence filter
+    let mut persistence = Persistence::new(config.persistence, DISPLAY_W * DISPLAY_H);
+    let mut elapsed_frames: u32 = 0;
+
+    // Reset and save states keep whole copies of the machine
+    let initial_state = cpu.clone();
+    let mut saved_state: Option<CPU> = None;
+
+    // Emulated time for the audio sinks, which keeps going forward when a
+    // reset or loaded state rewinds the machine
+    let mut audio_time = cpu.elapsed();
+    let mut audio_synced = cpu.elapsed();
+
+    // Debugger
+    let mut breakpoints = BTreeSet::new();
+    let mut prev_keys = [KeyState::NotPressed; KEY_SIZE]; // For key press and release edges
+    let mut step = false;
+    if config.debugger {
+        video.toggle_debugger();
+    }
 
     // Main loop
     let mut perf_counter: usize = 0;
-    let mut draw_queued = false;
-    while let Ok(keys) = input_driver.poll() {
+    let mut perf_draws: usize = 0;
+    let mut perf_frames: u32 = 0;
+    let mut perf_start = std::time::Instant::now();
+    let mut draw_queued = true;
+    let mut paused = false;
+    let mut frame_advance = false;
+    let mut slow_motion = false;
+    let mut movie_error = None;
+    'emulation: while let Ok(mut input_state) = input.poll() {
         let time_start = std::time::Instant::now();
 
-        // Generate inputs
-        let input = CycleInput {
-            keys,
-            decrement_timer: ch8_ticker.try_recv().is_ok(),
+        if config.frames.is_some_and(|frames| cpu.frame() >= frames) {
+            break;
+        }
+
+        // Closing the main window quits, other windows are the driver's
+        if input_state.closed.iter().any(|&id| !video.close_window(id)) {
+            break;
+        }
+
+        // On-screen keypad
+        if let Some(key) = input_state.pointer.and_then(|p| video.keypad_key(p)) {
+            input_state.keys[key] = KeyState::Pressed;
+        }
+
+        // Emulator controls
+        let prev_tone = tone;
+        for hotkey in input_state.hotkeys {
+            match hotkey {
+                Hotkey::Quit => break 'emulation,
+                // Movies only stay in sync when the machine runs uninterrupted
+                Hotkey::Reset | Hotkey::LoadState if movie.is_some() => {
+                    video.show_message("Not available while a movie is active");
+                }
+                Hotkey::Reset => {
+                    *cpu = initial_state.clone();
+                    audio_synced = cpu.elapsed();
+                    draw_queued = true;
+                    video.show_message("Reset");
+                }
+                Hotkey::SaveState => {
+                    saved_state = Some(cpu.clone());
+                    video.show_message("State saved");
+                }
+                Hotkey::LoadState => match &saved_state {
+                    Some(state) => {
+                        *cpu = state.clone();
+                        audio_synced = cpu.elapsed();
+                        draw_queued = true;
+                        video.show_message("State loaded");
+                    }
+                    None => video.show_message("No saved state"),
+                },
+                Hotkey::Pause => {
+                    paused = !paused;
+                    video.show_message(if paused { "Paused" } else { "Resumed" });
+                }
+                Hotkey::FrameAdvance => {
+                    paused = true;
+                    frame_advance = true;
+                }
+                Hotkey::SlowMotion => {
+                    slow_motion = !slow_motion;
+                    video.show_message(match slow_motion {
+                        true => "Slow motion on",
+                        false => "Slow motion off",
+                    });
+                }
+                Hotkey::Fullscreen => video.toggle_fullscreen(),
+                Hotkey::Stats => video.toggle_stats(),
+                Hotkey::Debugger => video.toggle_debugger(),
+                Hotkey::SpriteViewer => video.toggle_sprite_viewer(),
+                Hotkey::Step => {
+                    paused = true;
+                    step = true;
+                }
+                Hotkey::VolumeUp | Hotkey::VolumeDown => {
+                    let delta = match hotkey {
+                        Hotkey::VolumeUp => VOLUME_STEP,
+                        _ => -VOLUME_STEP,
+                    };
+                    tone.volume = (tone.volume + delta).clamp(0.0, 1.0);
+                    tone.muted = false;
+                    video.show_message(&format!("Volume {:.0}%", tone.volume * 100.0));
+                }
+                Hotkey::Mute => {
+                    tone.muted = !tone.muted;
+                    video.show_message(if tone.muted { "Muted" } else { "Unmuted" });
+                }
+                Hotkey::Waveform => {
+                    tone.waveform = tone.waveform.next();
+                    video.show_message(&format!("Waveform {}", tone.waveform));
+                }
+                Hotkey::Screenshot => {
+                    let path = output_path(config, cpu.frame(), "png");
+                    let pixels = persistence.apply(cpu.gfx(), 0);
+                    save_screenshot(config, &path, pixels, &mut video);
+                }
+                Hotkey::Record => match recorder.take() {
+                    Some(recorder) => stop_recording(recorder, &mut video),
+                    None => {
+                        let path = output_path(config, cpu.frame(), "gif");
+                        recorder = start_recording(config, &path, Format::Gif, tone, &mut video);
+                    }
+                },
+            }
+        }
+        if tone != prev_tone {
+            audio.set_tone(tone);
+            if let Some(r) = &mut recorder {
+                r.set_tone(tone);
+            }
+            if let Some(w) = &mut wav {
+                w.set_tone(tone);
+            }
+        }
+        for &click in &input_state.clicks {
+            match video.click(click) {
+                Some(ToolAction::ToggleBreakpoint(addr)) => {
+                    if breakpoints.contains(&addr) {
+                        breakpoints.remove(&addr);
+                    } else {
+                        breakpoints.insert(addr);
+                    }
+                }
+                Some(ToolAction::ExportSprites(sprites)) => {
+                    save_sprites(config, cpu.memory(), &sprites, &mut video);
+                }
+                None => (),
+            }
+        }
+        for &scroll in &input_state.scrolls {
+            video.scroll(scroll);
+        }
+        draw_queued |= input_state.redraw;
+
+        if paused && !frame_advance && !step {
+            audio.beep(false);
+            if draw_ticker.try_recv().is_ok() {
+                let pixels = match std::mem::take(&mut draw_queued) {
+                    true => Some(persistence.apply(cpu.gfx(), 0)),
+                    false => None,
+                };
+                video.update_keypad(&input_state.keys, polled);
+                video.draw(pixels, &cpu.registers(), None);
+                video.draw_tools(&DebugView {
+                    regs: cpu.registers(),
+                    memory: cpu.memory(),
+                    keys: &input_state.keys,
+                    breakpoints: &breakpoints,
+                });
+            }
+            spin_sleep::sleep(PAUSE_SLEEP_DURATION);
+            continue;
+        }
+
+        // Generate inputs, fixed for a whole frame when a movie records or
+        // plays
+        let live_keys = input_state.keys;
+        let keys = match movie.as_mut().map(|m| m.keys(&live_keys)) {
+            Some(Some(keys)) => keys,
+            // Headless runs have no live input to continue with
+            Some(None) if config.frontend == Frontend::Null => break,
+            Some(None) => {
+                video.show_message("Movie finished");
+                movie = None;
+                live_keys
+            }
+            None => live_keys,
         };
+        let input = CycleInput::new(keys, &prev_keys);
+        prev_keys = keys;
 
         // Run 1 CPU cycle
         let output = cpu.cycle(&input);
 
+        frame_beep |= output.beep;
+        if let Some(key) = output.polled_key {
+            frame_polled |= 1 << key;
+        }
+        if output.timer_tick {
+            polled = std::mem::take(&mut frame_polled);
+
+            // Stop after a single frame when advancing frame by frame
+            frame_advance = false;
+
+            if let Some(r) = &mut recorder {
+                if let Err(e) = r.frame(output.gfx, frame_beep) {
+                    video.show_message(&format!("Recording stopped: {}", e));
+                    recorder = None;
+                }
+            }
+            frame_beep = false;
+
+            // Keep redrawing while pixels fade out
+            elapsed_frames += 1;
+            perf_frames += 1;
+            draw_queued |= persistence.fading();
+        }
+        draw_queued |= CPUState::RunningDraw == output.state;
+
+        let beep = output.beep;
+        let timer_tick = output.timer_tick;
+        audio_time += cpu.elapsed() - audio_synced;
+        audio_synced = cpu.elapsed();
+        audio.beep(beep);
+        audio.sync(audio_time);
+        if timer_tick {
+            if let Some(Err(e)) = movie.as_mut().map(|m| m.end_frame(cpu.state_hash())) {
+                // Headless runs are for reproducing bugs and CI, so a desync
+                // ends them
+                if config.frontend == Frontend::Null {
+                    movie_error = Some(e);
+                    break;
+                }
+                video.show_message(&e);
+                movie = None;
+            }
+        }
+        if let Some(w) = &mut wav {
+            if let Err(e) = w.record_until(audio_time, beep) {
+                video.show_message(&format!("Audio recording stopped: {}", e));
+                wav = None;
+            }
+        }
+
+        // Stop before executing an instruction with a breakpoint
+        step = false;
+        if !breakpoints.is_empty() && breakpoints.contains(&cpu.registers().PC) {
+            paused = true;
+            frame_advance = false;
+            video.show_message(&format!("Breakpoint at {:03X}", cpu.registers().PC));
+        }
+
         // Performance monitoring
         perf_counter += 1;
-        let perf = match perf_ticker.try_recv() {
+        let stats = match perf_ticker.try_recv() {
             Ok(_) => {
-                let temp = perf_counter;
+                let frames = perf_frames.max(1);
+                let stats = Stats {
+                    ips: perf_counter,
+                    fps: perf_draws,
+                    frame_time: perf_start.elapsed() / frames,
+                    instructions_per_frame: perf_counter as f32 / frames as f32,
+                };
                 perf_counter = 0;
-                Some(temp)
-            },
+                perf_draws = 0;
+                perf_frames = 0;
+                perf_start = std::time::Instant::now();
+                Some(stats)
+            }
             Err(_) => None,
         };
 
         // Process outputs
-        let mut draw = || display_driver.draw(output.gfx, perf);
-        let draw_tick = draw_ticker.try_recv().is_ok();
-        if CPUState::RunningDraw == output.state {
-            if draw_tick {
-                draw();
-                draw_queued = false;
-            } else {
-                draw_queued = true;
-            }
-        } else if draw_tick && draw_queued || perf.is_some() {
-            draw();
-            draw_queued = false;
+        if draw_ticker.try_recv().is_ok() || stats.is_some() {
+            let pixels = match std::mem::take(&mut draw_queued) {
+                true => {
+                    let elapsed = std::mem::take(&mut elapsed_frames);
+                    perf_draws += 1;
+                    Some(persistence.apply(cpu.gfx(), elapsed))
+                }
+                false => None,
+            };
+            video.update_keypad(&keys, polled);
+            video.draw(pixels, &cpu.registers(), stats);
+            video.draw_tools(&DebugView {
+                regs: cpu.registers(),
+                memory: cpu.memory(),
+                keys: &input_state.keys,
+                breakpoints: &breakpoints,
+            });
         }
 
-        audio_driver.beep(output.beep);
+        // Scale instruction duration by the current speed, timers and audio
+        // follow since they run off emulated time
+        let target_duration = if input_state.fast_forward {
+            target_sleep_duration / config.fast_forward
+        } else if slow_motion {
+            target_sleep_duration * config.slow_motion
+        } else {
+            target_sleep_duration
+        };
 
         // sleep remaining duration
         let time_end = std::time::Instant::now();
         let time_elapsed = time_end - time_start;
-        let sleep_duration = target_sleep_duration
+        let sleep_duration = target_duration
             .checked_sub(time_elapsed)
             .unwrap_or(std::time::Duration::from_nanos(0));
         spin_sleep::sleep(sleep_duration);
     }
+
+    // Nothing is drawn any more, so only failures are reported
+    if let Some(Err(e)) = recorder.map(Recorder::finish) {
+        writeln!(&mut stderr(), "Recording failed: {}", e).ok();
+    }
+    if let Some(Err(e)) = movie.map(Movie::finish) {
+        writeln!(&mut stderr(), "Movie recording failed: {}", e).ok();
+    }
+    if let Some(Err(e)) = wav.map(WavRecorder::finish) {
+        writeln!(&mut stderr(), "Audio recording failed: {}", e).ok();
+    }
+    if let Some(e) = movie_error {
+        writeln!(&mut stderr(), "{}", e).ok();
+        std::process::exit(1);
+    }
+}
+
+/// Save a screenshot with the configured palette and scale, returns whether
+/// it succeeded
+fn save_screenshot<V: Video>(config: &Config, path: &Path, pixels: &[f32], video: &mut V) -> bool {
+    let result = screenshot::save_png(
+        path,
+        pixels,
+        DISPLAY_W,
+        DISPLAY_H,
+        &config.palette,
+        config.scale,
+    );
+    match result {
+        Ok(()) => {
+            video.show_message(&format!("Saved screenshot {}", path.display()));
+            true
+        }
+        Err(e) => {
+            video.show_message(&format!("{:?}: {}", path, e));
+            false
+        }
+    }
+}
+
+/// Save sprites side by side as a PNG named after the first one's address
+fn save_sprites<V: Video>(config: &Config, memory: &[u8], sprites: &[Sprite], video: &mut V) {
+    let path = output_path(config, format!("{:03X}", sprites[0].addr), "png");
+    let (pixels, width, height) = Sprite::sheet(sprites, memory);
+    let result = screenshot::save_png(&path, &pixels, width, height, &config.palette, config.scale);
+    match result {
+        Ok(()) => video.show_message(&format!("Saved sprites {}", path.display())),
+        Err(e) => video.show_message(&format!("{:?}: {}", path, e)),
+    }
+}
+
+/// Start recording to `path`, with audio next to it if enabled
+fn start_recording<V: Video>(
+    config: &Config,
+    path: &Path,
+    format: Format,
+    tone: Tone,
+    video: &mut V,
+) -> Option<Recorder> {
+    let audio_path = match config.record_audio {
+        true if path == Path::new("-") => Some(output_path(config, 0, "wav")),
+        true => Some(path.with_extension("wav")),
+        false => None,
+    };
+    let result = Recorder::start(
+        path,
+        audio_path.as_deref().map(|path| (path, tone)),
+        format,
+        DISPLAY_W,
+        DISPLAY_H,
+        config.palette,
+        config.scale,
+    );
+    match result {
+        Ok(recorder) => {
+            video.show_message(&format!("Recording to {}", path.display()));
+            Some(recorder)
+        }
+        Err(e) => {
+            video.show_message(&format!("{:?}: {}", path, e));
+            None
+        }
+    }
+}
+
+fn stop_recording<V: Video>(recorder: Recorder, video: &mut V) {
+    match recorder.finish() {
+        Ok(()) => video.show_message("Recording saved"),
+        Err(e) => video.show_message(&format!("Recording failed: {}", e)),
+    }
+}
+
+/// "<ROM name>_<tag>.<extension>" in the current directory, the tag is
+/// usually the frame number
+fn output_path(config: &Config, tag: impl std::fmt::Display, extension: &str) -> PathBuf {
+    let stem = Path::new(&config.rom_file)
+        .file_stem()
+        .unwrap_or_else(|| std::ffi::OsStr::new("chip8"))
+        .to_string_lossy();
+    PathBuf::from(format!("{}_{}.{}", stem, tag, extension))
 }
@@ -0,0 +1,285 @@
+use crate::cpu::{KeyState, KEY_SIZE};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+// Movies are text files:
+//   chip8-movie 1
+//   rom <SHA-1 of the ROM>
+//   seed <random number seed>
+//   rate <instructions per second>
+// followed by one line per 60Hz frame holding the pressed keys as a hex bit
+// mask (bit N is key N), with a "hash <state hash>" line after every
+// HASH_INTERVAL frames.
+const MAGIC: &str = "chip8-movie 1";
+const HASH_INTERVAL: u64 = 60;
+
+/// What a movie must be replayed with to stay in sync
+pub struct MovieHeader {
+    pub rom_hash: String,
+    pub seed: u64,
+    pub rate: u64,
+}
+
+/// Recording or playback of keypad input, one keypad state per 60Hz frame.
+/// Keys only change at frame boundaries so replays are exact.
+pub enum Movie {
+    Record {
+        writer: BufWriter<File>,
+        frame: u64,
+        keys: Option<[KeyState; KEY_SIZE]>, // Keys of the current frame
+    },
+    Play {
+        frames: Vec<u16>,
+        hashes: Vec<(u64, u64)>, // Frame and the state hash after it
+        frame: u64,
+    },
+}
+
+impl MovieHeader {
+    pub fn new(rom: &[u8], seed: u64, rate: u64) -> Self {
+        Self {
+            rom_hash: sha1_smol::Sha1::from(rom).digest().to_string(),
+            seed,
+            rate,
+        }
+    }
+}
+
+impl Movie {
+    pub fn record(path: &Path, header: &MovieHeader) -> io::Result<Self> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writeln!(writer, "{}", MAGIC)?;
+        writeln!(writer, "rom {}", header.rom_hash)?;
+        writeln!(writer, "seed {}", header.seed)?;
+        writeln!(writer, "rate {}", header.rate)?;
+        Ok(Movie::Record {
+            writer,
+            frame: 0,
+            keys: None,
+        })
+    }
+
+    pub fn play(path: &Path) -> Result<(Self, MovieHeader), String> {
+        let text = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        let mut lines = text.lines();
+        if lines.next() != Some(MAGIC) {
+            return Err("not a movie file".to_string());
+        }
+
+        let mut field = |name: &str| {
+            lines
+                .next()
+                .and_then(|line| line.strip_prefix(name))
+                .map(|value| value.trim().to_string())
+                .ok_or_else(|| format!("missing {}", name))
+        };
+        let rom_hash = field("rom")?;
+        let seed = field("seed")?.parse().map_err(|_| "invalid seed")?;
+        let rate = field("rate")?.parse().map_err(|_| "invalid rate")?;
+
+        let mut frames = Vec::new();
+        let mut hashes = Vec::new();
+        for line in lines {
+            if let Some(hash) = line.strip_prefix("hash ") {
+                let hash = u64::from_str_radix(hash, 16).map_err(|_| "invalid hash")?;
+                hashes.push((frames.len() as u64, hash));
+            } else {
+                let keys = u16::from_str_radix(line, 16).map_err(|_| "invalid frame")?;
+                frames.push(keys);
+            }
+        }
+
+        let header = MovieHeader {
+            rom_hash,
+            seed,
+            rate,
+        };
+        let movie = Movie::Play {
+            frames,
+            hashes,
+            frame: 0,
+        };
+        Ok((movie, header))
+    }
+
+    /// Keys for the current frame, `live` when recording. `None` once
+    /// playback reached the end.
+    pub fn keys(&mut self, live: &[KeyState; KEY_SIZE]) -> Option<[KeyState; KEY_SIZE]> {
+        match self {
+            Movie::Record { keys, .. } => Some(*keys.get_or_insert(*live)),
+            Movie::Play { frames, frame, .. } => {
+                let mask = *frames.get(*frame as usize)?;
+                let mut keys = [KeyState::NotPressed; KEY_SIZE];
+                for (key, state) in keys.iter_mut().enumerate() {
+                    if mask & (1 << key) != 0 {
+                        *state = KeyState::Pressed;
+                    }
+                }
+                Some(keys)
+            }
+        }
+    }
+
+    /// Finish the current frame. When playing back, fails if the state hash
+    /// differs from the recording.
+    pub fn end_frame(&mut self, state_hash: u64) -> Result<(), String> {
+        match self {
+            Movie::Record {
+                writer,
+                frame,
+                keys,
+            } => {
+                let mask = keys
+                    .take()
+                    .unwrap_or([KeyState::NotPressed; KEY_SIZE])
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, &state)| state == KeyState::Pressed)
+                    .fold(0u16, |mask, (key, _)| mask | 1 << key);
+                let result = writeln!(writer, "{:04x}", mask).and_then(|_| {
+                    *frame += 1;
+                    match *frame % HASH_INTERVAL {
+                        0 => writeln!(writer, "hash {:016x}", state_hash),
+                        _ => Ok(()),
+                    }
+                });
+                result.map_err(|e| format!("Movie recording stopped: {}", e))
+            }
+            Movie::Play { hashes, frame, .. } => {
+                *frame += 1;
+                match hashes.binary_search_by_key(frame, |&(frame, _)| frame) {
+                    Ok(index) if hashes[index].1 != state_hash => {
+                        Err(format!("Movie desync at frame {}", frame))
+                    }
+                    _ => Ok(()),
+                }
+            }
+        }
+    }
+
+    /// Flush a recording to disk
+    pub fn finish(self) -> io::Result<()> {
+        match self {
+            Movie::Record { mut writer, .. } => writer.flush(),
+            Movie::Play { .. } => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys(mask: u16) -> [KeyState; KEY_SIZE] {
+        let mut keys = [KeyState::NotPressed; KEY_SIZE];
+        for (key, state) in keys.iter_mut().enumerate() {
+            if mask & (1 << key) != 0 {
+                *state = KeyState::Pressed;
+            }
+        }
+        keys
+    }
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("chip8_emu_{}_{}", std::process::id(), name))
+    }
+
+    // Record 130 frames where the state hash is the frame number
+    fn record(path: &Path) -> MovieHeader {
+        let header = MovieHeader::new(&[0x12, 0x00], 42, 700);
+        let mut movie = Movie::record(path, &header).unwrap();
+        for frame in 0..130u16 {
+            assert!(movie.keys(&keys(frame)).is_some());
+            // Keys only change at frame boundaries
+            assert!(movie.keys(&keys(0xFFFF)) == Some(keys(frame)));
+            movie.end_frame(u64::from(frame) + 1).unwrap();
+        }
+        movie.finish().unwrap();
+        header
+    }
+
+    #[test]
+    fn hash_lines_follow_every_interval() {
+        let path = temp_path("hashes.movie");
+        let header = record(&path);
+        let text = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(
+            lines[..4],
+            [
+                MAGIC,
+                &format!("rom {}", header.rom_hash),
+                "seed 42",
+                "rate 700"
+            ]
+        );
+        assert_eq!(lines[4], "0000");
+        assert_eq!(lines[4 + 59], "003b");
+        assert_eq!(lines[4 + 60], "hash 000000000000003c");
+        assert_eq!(lines[4 + 61], "003c");
+        assert_eq!(lines[4 + 121], "hash 0000000000000078");
+        assert_eq!(lines.len(), 4 + 130 + 2);
+    }
+
+    #[test]
+    fn playback_round_trip() {
+        let path = temp_path("round_trip.movie");
+        let recorded = record(&path);
+        let played = Movie::play(&path);
+        std::fs::remove_file(&path).ok();
+
+        let (mut movie, header) = played.unwrap();
+        assert_eq!(header.rom_hash, recorded.rom_hash);
+        assert_eq!((header.seed, header.rate), (42, 700));
+        for frame in 0..130u16 {
+            assert!(movie.keys(&keys(0xFFFF)) == Some(keys(frame)));
+            movie.end_frame(u64::from(frame) + 1).unwrap();
+        }
+        assert!(movie.keys(&keys(0)).is_none());
+    }
+
+    #[test]
+    fn playback_detects_desync() {
+        let path = temp_path("desync.movie");
+        record(&path);
+        let played = Movie::play(&path);
+        std::fs::remove_file(&path).ok();
+
+        let (mut movie, _) = played.unwrap();
+        for frame in 1..60 {
+            movie.end_frame(0).unwrap();
+            assert!(movie.keys(&keys(0)) == Some(keys(frame)));
+        }
+        assert_eq!(
+            movie.end_frame(0).err().unwrap(),
+            "Movie desync at frame 60"
+        );
+    }
+
+    #[test]
+    fn invalid_movies() {
+        let path = temp_path("invalid.movie");
+        let play = |text: &str| {
+            std::fs::write(&path, text).unwrap();
+            Movie::play(&path).err().unwrap()
+        };
+        assert_eq!(play("chip8-movie 2\n"), "not a movie file");
+        assert_eq!(play("chip8-movie 1\nrom abc\nrate 700\n"), "missing seed");
+        assert_eq!(
+            play("chip8-movie 1\nrom abc\nseed x\nrate 700\n"),
+            "invalid seed"
+        );
+        assert_eq!(
+            play("chip8-movie 1\nrom abc\nseed 1\nrate 700\nzz\n"),
+            "invalid frame"
+        );
+        assert_eq!(
+            play("chip8-movie 1\nrom abc\nseed 1\nrate 700\nhash z\n"),
+            "invalid hash"
+        );
+        std::fs::remove_file(&path).ok();
+    }
+}
@@ -0,0 +1,91 @@
+use clap::arg_enum;
+use std::str::FromStr;
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct Rgb {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl Rgb {
+    pub const fn new(r: u8, g: u8, b: u8) -> Self {
+        Self { r, g, b }
+    }
+
+    const fn hex(rgb: u32) -> Self {
+        Self::new((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8)
+    }
+}
+
+/// Parses "RRGGBB" or "#RRGGBB"
+impl FromStr for Rgb {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.strip_prefix('#').unwrap_or(s);
+        if s.len() != 6 {
+            return Err(());
+        }
+        u32::from_str_radix(s, 16).map(Self::hex).map_err(|_| ())
+    }
+}
+
+arg_enum! {
+    #[derive(Clone, Copy, PartialEq)]
+    pub enum Theme {
+        Default,
+        VIP,
+        HP48,
+        Octo,
+        Amber,
+        Green,
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct Palette {
+    pub background: Rgb,
+    pub foreground: Rgb,
+}
+
+impl Palette {
+    pub fn from_theme(theme: Theme) -> Self {
+        let (background, foreground) = match theme {
+            Theme::Default => (0x000000, 0xFFFFFF),
+            // Television output of the RCA COSMAC VIP
+            Theme::VIP => (0x111111, 0xE8E8E8),
+            // Reflective LCD of the HP 48 calculators
+            Theme::HP48 => (0x87987A, 0x1F2A1C),
+            // Octo's default color scheme
+            Theme::Octo => (0x996600, 0xFFCC00),
+            // Amber and green phosphor monochrome monitors
+            Theme::Amber => (0x1A0F00, 0xFFB000),
+            Theme::Green => (0x001A00, 0x33FF33),
+        };
+
+        Self {
+            background: Rgb::hex(background),
+            foreground: Rgb::hex(foreground),
+        }
+    }
+
+    /// Blend between background (0.0) and foreground (1.0)
+    pub fn shade(&self, intensity: f32) -> Rgb {
+        let mix =
+            |bg: u8, fg: u8| (f32::from(bg) + (f32::from(fg) - f32::from(bg)) * intensity) as u8;
+        Rgb::new(
+            mix(self.background.r, self.foreground.r),
+            mix(self.background.g, self.foreground.g),
+            mix(self.background.b, self.foreground.b),
+        )
+    }
+
+    /// Convert pixel brightness values to packed RGBA bytes
+    pub fn fill_rgba(&self, pixels: &[f32], rgba: &mut [u8]) {
+        for (&intensity, out) in pixels.iter().zip(rgba.chunks_exact_mut(4)) {
+            let color = self.shade(intensity);
+            out.copy_from_slice(&[color.r, color.g, color.b, 0xFF]);
+        }
+    }
+}
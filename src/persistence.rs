@@ -0,0 +1,46 @@
+use crate::cpu::PixelState;
+
+/// Phosphor persistence filter. Pixels that turn off fade out over a number of
+/// 60Hz frames instead of disappearing at once, which hides the flicker of
+/// sprites that are erased and redrawn with XOR.
+pub struct Persistence {
+    frames: u32,         // Frames for a pixel to fade out, 0 to disable
+    intensity: Vec<f32>, // Per-pixel brightness in [0, 1]
+    fading: bool,        // Some pixels are off but still lit
+}
+
+impl Persistence {
+    pub fn new(frames: u32, size: usize) -> Self {
+        Self {
+            frames,
+            intensity: vec![0.0; size],
+            fading: false,
+        }
+    }
+
+    /// Update pixel brightness from the framebuffer, `elapsed` is the number of
+    /// frames since the last update
+    pub fn apply(&mut self, gfx: &[PixelState], elapsed: u32) -> &[f32] {
+        let fade = match self.frames {
+            0 => 1.0,
+            frames => elapsed as f32 / frames as f32,
+        };
+
+        self.fading = false;
+        for (intensity, pixel) in self.intensity.iter_mut().zip(gfx) {
+            *intensity = match pixel {
+                PixelState::On => 1.0,
+                PixelState::Off if self.frames == 0 => 0.0,
+                PixelState::Off => (*intensity - fade).max(0.0),
+            };
+            self.fading |= *pixel == PixelState::Off && *intensity > 0.0;
+        }
+
+        &self.intensity
+    }
+
+    /// Whether any pixel is still fading out
+    pub fn fading(&self) -> bool {
+        self.fading
+    }
+}
@@ -0,0 +1,69 @@
+use clap::arg_enum;
+
+arg_enum! {
+    /// When FX0A (wait for key) completes
+    #[derive(Clone, Copy, PartialEq)]
+    pub enum KeyWait {
+        Level,   // As soon as any key is held, a held key retriggers at once
+        Press,   // When a key goes down
+        Release, // When a key pressed during the wait goes up, like the COSMAC VIP
+    }
+}
+
+arg_enum! {
+    /// Which key FX0A stores when several are pressed while waiting
+    #[derive(Clone, Copy, PartialEq)]
+    pub enum KeyPriority {
+        First,  // The first one pressed
+        Lowest, // The lowest numbered one
+    }
+}
+
+/// Behaviors that differ between CHIP-8 interpreters
+#[derive(Clone, Copy)]
+pub struct Quirks {
+    pub key_wait: KeyWait,
+    pub key_priority: KeyPriority,
+    pub shift: bool, // 8XY6/8XYE shift VX in place instead of VY into VX
+    pub memory_increment_by_x: bool, // FX55/FX65 advance I by X instead of X + 1
+    pub memory_leave_i_unchanged: bool, // FX55/FX65 leave I unchanged
+    pub wrap: bool,  // Sprites wrap around the display edges instead of clipping
+    pub jump: bool,  // BXNN jumps to XNN + VX instead of NNN + V0
+    pub vblank: bool, // DXYN waits for the next 60Hz frame
+    pub logic: bool, // 8XY1/8XY2/8XY3 reset VF
+}
+
+impl Default for Quirks {
+    fn default() -> Self {
+        Self {
+            key_wait: KeyWait::Release,
+            key_priority: KeyPriority::First,
+            shift: true,
+            memory_increment_by_x: false,
+            memory_leave_i_unchanged: true,
+            wrap: false,
+            jump: false,
+            vblank: false,
+            logic: false,
+        }
+    }
+}
+
+impl Quirks {
+    /// Set a quirk by its name in the CHIP-8 database, returns `false` for
+    /// unknown names
+    pub fn set(&mut self, name: &str, value: bool) -> bool {
+        let quirk = match name {
+            "shift" => &mut self.shift,
+            "memoryIncrementByX" => &mut self.memory_increment_by_x,
+            "memoryLeaveIUnchanged" => &mut self.memory_leave_i_unchanged,
+            "wrap" => &mut self.wrap,
+            "jump" => &mut self.jump,
+            "vblank" => &mut self.vblank,
+            "logic" => &mut self.logic,
+            _ => return false,
+        };
+        *quirk = value;
+        true
+    }
+}
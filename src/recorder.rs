@@ -0,0 +1,219 @@
+use crate::cpu::PixelState;
+use crate::palette::Palette;
+use crate::synth::Tone;
+use crate::wav::WavRecorder;
+use std::convert::TryFrom;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::time::Duration;
+
+// Browsers slow down GIF frames shorter than 2 centiseconds, so frames are
+// dropped to keep at or above this delay
+const GIF_MIN_DELAY: u64 = 2;
+const GIF_MAX_DELAY: u64 = u16::MAX as u64 / 2;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Format {
+    Gif, // Animated GIF, scaled
+    Raw, // Unscaled RGB24 frames at 60fps, e.g. for ffmpeg's rawvideo demuxer
+}
+
+/// Records emulated video, one frame per 60Hz tick, with optional audio
+pub struct Recorder {
+    video: Video,
+    audio: Option<WavRecorder>,
+    frames: u32, // Frames recorded so far
+}
+
+enum Video {
+    Gif(GifVideo),
+    Raw(Box<dyn Write>, Palette),
+}
+
+struct GifVideo {
+    encoder: gif::Encoder<BufWriter<File>>,
+    width: usize,
+    height: usize,
+    scale: usize,
+    pending: Option<Vec<u8>>, // Last frame, written once its duration is known
+    pending_frames: u64,      // Emulated frames the pending frame lasts
+    frames_written: u64,      // Emulated frames covered by written GIF frames
+    delay_written: u64,       // Sum of written GIF frame delays in centiseconds
+}
+
+impl Recorder {
+    /// Start recording `width` by `height` frames to `path`, "-" writes raw
+    /// frames to stdout. Audio is written with the given tone if a path is
+    /// given.
+    pub fn start(
+        path: &Path,
+        audio: Option<(&Path, Tone)>,
+        format: Format,
+        width: usize,
+        height: usize,
+        palette: Palette,
+        scale: u32,
+    ) -> io::Result<Self> {
+        let video = match format {
+            Format::Gif => {
+                let scale = scale as usize;
+                // GIF dimensions are 16 bit
+                let size = |pixels: usize| {
+                    pixels
+                        .checked_mul(scale)
+                        .and_then(|size| u16::try_from(size).ok())
+                        .ok_or_else(|| {
+                            io::Error::new(
+                                io::ErrorKind::InvalidInput,
+                                format!("scale {} is too large for a GIF", scale),
+                            )
+                        })
+                };
+                let (gif_width, gif_height) = (size(width)?, size(height)?);
+                let global_palette = [
+                    palette.background.r,
+                    palette.background.g,
+                    palette.background.b,
+                    palette.foreground.r,
+                    palette.foreground.g,
+                    palette.foreground.b,
+                ];
+                let file = BufWriter::new(File::create(path)?);
+                let mut encoder = gif::Encoder::new(file, gif_width, gif_height, &global_palette)
+                    .map_err(io::Error::other)?;
+                encoder
+                    .set_repeat(gif::Repeat::Infinite)
+                    .map_err(io::Error::other)?;
+                Video::Gif(GifVideo {
+                    encoder,
+                    width,
+                    height,
+                    scale,
+                    pending: None,
+                    pending_frames: 0,
+                    frames_written: 0,
+                    delay_written: 0,
+                })
+            }
+            Format::Raw if path == Path::new("-") => Video::Raw(Box::new(io::stdout()), palette),
+            Format::Raw => Video::Raw(Box::new(BufWriter::new(File::create(path)?)), palette),
+        };
+
+        let audio = match audio {
+            Some((audio_path, tone)) => Some(WavRecorder::create(audio_path, tone)?),
+            None => None,
+        };
+
+        Ok(Self {
+            video,
+            audio,
+            frames: 0,
+        })
+    }
+
+    /// Record one 60Hz frame, `beep` is whether the buzzer sounded during it
+    pub fn frame(&mut self, gfx: &[PixelState], beep: bool) -> io::Result<()> {
+        match &mut self.video {
+            Video::Gif(gif) => gif.frame(gfx)?,
+            Video::Raw(writer, palette) => {
+                let mut rgb = Vec::with_capacity(gfx.len() * 3);
+                for pixel in gfx {
+                    let color = match pixel {
+                        PixelState::On => palette.foreground,
+                        PixelState::Off => palette.background,
+                    };
+                    rgb.extend_from_slice(&[color.r, color.g, color.b]);
+                }
+                writer.write_all(&rgb)?;
+            }
+        }
+
+        self.frames += 1;
+        if let Some(audio) = &mut self.audio {
+            audio.record_until(Duration::from_secs(1) * self.frames / 60, beep)?;
+        }
+
+        Ok(())
+    }
+
+    /// Change the tone of recorded audio
+    pub fn set_tone(&mut self, tone: Tone) {
+        if let Some(audio) = &mut self.audio {
+            audio.set_tone(tone);
+        }
+    }
+
+    /// Flush remaining frames and finalize the output files
+    pub fn finish(self) -> io::Result<()> {
+        match self.video {
+            Video::Gif(mut gif) => {
+                gif.flush()?;
+                gif.encoder.into_inner()?.flush()?;
+            }
+            Video::Raw(mut writer, _) => writer.flush()?,
+        }
+
+        if let Some(audio) = self.audio {
+            audio.finish()?;
+        }
+
+        Ok(())
+    }
+}
+
+impl GifVideo {
+    fn frame(&mut self, gfx: &[PixelState]) -> io::Result<()> {
+        let mut indices = Vec::with_capacity(gfx.len() * self.scale * self.scale);
+        for row in gfx.chunks_exact(self.width) {
+            for _ in 0..self.scale {
+                for pixel in row {
+                    let index = match pixel {
+                        PixelState::On => 1,
+                        PixelState::Off => 0,
+                    };
+                    indices.extend(std::iter::repeat_n(index, self.scale));
+                }
+            }
+        }
+
+        if self.pending.as_ref() == Some(&indices) && self.pending_delay() < GIF_MAX_DELAY {
+            // Unchanged frames extend the duration of the pending one
+            self.pending_frames += 1;
+        } else if self.pending.is_some() && self.pending_delay() < GIF_MIN_DELAY {
+            // Too short to display, replace it
+            self.pending = Some(indices);
+            self.pending_frames += 1;
+        } else {
+            self.flush()?;
+            self.pending = Some(indices);
+            self.pending_frames = 1;
+        }
+
+        Ok(())
+    }
+
+    /// Delay of the pending frame in centiseconds, rounded so the total
+    /// duration tracks emulated time
+    fn pending_delay(&self) -> u64 {
+        (self.frames_written + self.pending_frames) * 100 / 60 - self.delay_written
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if let Some(indices) = self.pending.take() {
+            let delay = self.pending_delay();
+            let frame = gif::Frame {
+                width: (self.width * self.scale) as u16,
+                height: (self.height * self.scale) as u16,
+                delay: delay as u16,
+                buffer: indices.into(),
+                ..gif::Frame::default()
+            };
+            self.encoder.write_frame(&frame).map_err(io::Error::other)?;
+            self.frames_written += self.pending_frames;
+            self.delay_written += delay;
+            self.pending_frames = 0;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,43 @@
+use crate::palette::Palette;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+
+/// Write pixel brightness values as an RGB PNG, each pixel enlarged to a
+/// `scale` by `scale` square
+pub fn save_png(
+    path: &Path,
+    pixels: &[f32],
+    width: usize,
+    height: usize,
+    palette: &Palette,
+    scale: u32,
+) -> std::io::Result<()> {
+    let scale = scale as usize;
+    let mut rgba = vec![0; pixels.len() * 4];
+    palette.fill_rgba(pixels, &mut rgba);
+
+    let mut data = Vec::with_capacity(width * height * scale * scale * 3);
+    for row in rgba.chunks_exact(width * 4) {
+        for _ in 0..scale {
+            for pixel in row.chunks_exact(4) {
+                for _ in 0..scale {
+                    data.extend_from_slice(&pixel[..3]);
+                }
+            }
+        }
+    }
+
+    let file = File::create(path)?;
+    let mut encoder = png::Encoder::new(
+        BufWriter::new(file),
+        (width * scale) as u32,
+        (height * scale) as u32,
+    );
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&data)?;
+
+    Ok(())
+}
@@ -0,0 +1,211 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+// Options that can be set in the config file, by command line name
+const KEYS: &[&str] = &[
+    "rate",
+    "key-map",
+    "key-map-file",
+    "hotkeys",
+    "key-wait",
+    "key-priority",
+    "platform",
+    "rom-db",
+    "theme",
+    "fg",
+    "bg",
+    "beep-freq",
+    "waveform",
+    "volume",
+    "mute",
+    "no-audio",
+];
+
+// Settings holding file paths, relative to the config file
+const PATH_KEYS: &[&str] = &["key-map-file", "hotkeys", "rom-db"];
+
+// Settings that replace each other, a ROM section setting one of them drops
+// the others set for all ROMs
+const ALTERNATIVES: &[&[&str]] = &[&["key-map", "key-map-file"]];
+
+/// Defaults from the TOML config file. Top level keys apply to every ROM,
+/// tables under `[rom."<name>"]` to ROMs with that file name or SHA-1 hash,
+/// the hash taking precedence:
+///
+/// ```toml
+/// theme = "Amber"
+/// volume = 30
+///
+/// [rom."BRIX"]
+/// rate = 1000
+/// key-wait = "Press"
+/// ```
+///
+/// Keys are the long command line options, which take precedence. Switches
+/// like `mute` that are set to true here can't be turned off on the command
+/// line.
+#[derive(Default)]
+pub struct Settings {
+    values: HashMap<String, String>,
+}
+
+impl Settings {
+    /// `config.toml` in the user config directory, e.g. `~/.config/chip8_emu`
+    pub fn default_path() -> Option<PathBuf> {
+        Some(
+            dirs::config_dir()?
+                .join(env!("CARGO_PKG_NAME"))
+                .join("config.toml"),
+        )
+    }
+
+    /// Settings for the ROM at `rom_file` with SHA-1 `rom_hash`
+    pub fn load(path: &Path, rom_file: &Path, rom_hash: Option<&str>) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        let table: toml::Table = text.parse().map_err(|e: toml::de::Error| e.to_string())?;
+
+        let name = rom_file.file_name().map(|name| name.to_string_lossy());
+
+        let dir = path.parent().unwrap_or_else(|| Path::new(""));
+        let mut settings = Settings::default();
+        let mut roms = None;
+        for (key, value) in &table {
+            match (key.as_str(), value) {
+                ("rom", toml::Value::Table(table)) => roms = Some(table),
+                _ => settings.set(key, value, dir)?,
+            }
+        }
+        for section in [name.as_deref(), rom_hash].iter().flatten() {
+            let rom = roms.and_then(|roms| roms.get(*section));
+            match rom {
+                Some(toml::Value::Table(table)) => {
+                    for keys in ALTERNATIVES {
+                        if keys.iter().any(|key| table.contains_key(*key)) {
+                            for key in keys.iter() {
+                                settings.values.remove(*key);
+                            }
+                        }
+                    }
+                    for (key, value) in table {
+                        settings.set(key, value, dir)?;
+                    }
+                }
+                Some(_) => return Err(format!("rom.{:?} must be a table", section)),
+                None => (),
+            }
+        }
+        Ok(settings)
+    }
+
+    fn set(&mut self, key: &str, value: &toml::Value, dir: &Path) -> Result<(), String> {
+        if !KEYS.contains(&key) {
+            return Err(format!("unknown setting {:?}", key));
+        }
+        let value = match value {
+            toml::Value::String(s) => s.clone(),
+            toml::Value::Integer(i) => i.to_string(),
+            toml::Value::Float(f) => f.to_string(),
+            toml::Value::Boolean(b) => b.to_string(),
+            _ => return Err(format!("{} must be a string, number or boolean", key)),
+        };
+        let value = match PATH_KEYS.contains(&key) {
+            true => dir.join(value).to_string_lossy().into_owned(),
+            false => value,
+        };
+        self.values.insert(key.to_string(), value);
+        Ok(())
+    }
+
+    pub fn insert(&mut self, key: &str, value: String) {
+        self.values.insert(key.to_string(), value);
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.values.get(key).map(String::as_str)
+    }
+
+    /// Switches, `true` when set to true in the file
+    pub fn flag(&self, key: &str) -> bool {
+        self.get(key) == Some("true")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"
+theme = "Amber"
+volume = 30
+beep-freq = 261.63
+key-map-file = "maps/dvorak.txt"
+
+[rom."BRIX"]
+rate = 1000
+theme = "Green"
+key-map = "AZERTY"
+
+[rom."0123abcd"]
+rate = 1500
+"#;
+
+    fn load(name: &str, text: &str, rom_hash: Option<&str>) -> Result<Settings, String> {
+        let dir = std::env::temp_dir().join(format!("chip8_emu_{}_{}", std::process::id(), name));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.toml");
+        std::fs::write(&path, text).unwrap();
+        let settings = Settings::load(&path, Path::new("roms/BRIX"), rom_hash);
+        std::fs::remove_dir_all(&dir).ok();
+        settings
+    }
+
+    #[test]
+    fn global_settings() {
+        let settings = load("global", CONFIG, None).unwrap();
+        assert_eq!(settings.get("volume"), Some("30"));
+        assert_eq!(settings.get("beep-freq"), Some("261.63"));
+        assert_eq!(settings.get("mute"), None);
+        assert!(!settings.flag("mute"));
+    }
+
+    #[test]
+    fn rom_sections_by_name_then_hash() {
+        let settings = load("sections", CONFIG, Some("0123abcd")).unwrap();
+        assert_eq!(settings.get("theme"), Some("Green"));
+        assert_eq!(settings.get("rate"), Some("1500"));
+        let settings = load("name", CONFIG, Some("ffff")).unwrap();
+        assert_eq!(settings.get("rate"), Some("1000"));
+    }
+
+    #[test]
+    fn paths_are_relative_to_the_file() {
+        let text = "hotkeys = \"keys.txt\"";
+        let dir = std::env::temp_dir().join(format!("chip8_emu_{}_paths", std::process::id()));
+        let settings = load("paths", text, None).unwrap();
+        let expected = dir.join("keys.txt");
+        assert_eq!(settings.get("hotkeys"), Some(&*expected.to_string_lossy()));
+    }
+
+    #[test]
+    fn rom_key_map_replaces_global_key_map_file() {
+        let settings = load("key_map", CONFIG, None).unwrap();
+        assert_eq!(settings.get("key-map"), Some("AZERTY"));
+        assert_eq!(settings.get("key-map-file"), None);
+    }
+
+    #[test]
+    fn invalid_settings() {
+        assert_eq!(
+            load("unknown", "speed = 5", None).err().unwrap(),
+            "unknown setting \"speed\""
+        );
+        assert_eq!(
+            load("array", "volume = [1]", None).err().unwrap(),
+            "volume must be a string, number or boolean"
+        );
+        assert_eq!(
+            load("section", "[rom]\nBRIX = 1", None).err().unwrap(),
+            "rom.\"BRIX\" must be a table"
+        );
+    }
+}
@@ -0,0 +1,45 @@
+/// Sprite data in memory, each row is `width / 8` bytes
+#[derive(Clone, Copy, PartialEq)]
+pub struct Sprite {
+    pub addr: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Sprite {
+    /// Size in memory
+    pub fn len(&self) -> usize {
+        self.width / 8 * self.height
+    }
+
+    /// Whether the pixel at (x, y) is set, bytes past the end of memory are
+    /// read as 0
+    pub fn pixel(&self, memory: &[u8], x: usize, y: usize) -> bool {
+        let byte = self.addr + y * self.width / 8 + x / 8;
+        memory
+            .get(byte)
+            .is_some_and(|bits| bits & (0x80 >> (x % 8)) != 0)
+    }
+
+    /// Lay sprites out side by side, one pixel apart, as pixel brightness
+    /// values. Returns the pixels, width and height.
+    pub fn sheet(sprites: &[Sprite], memory: &[u8]) -> (Vec<f32>, usize, usize) {
+        let width = sprites.iter().map(|s| s.width + 1).sum::<usize>().max(1) - 1;
+        let height = sprites.iter().map(|s| s.height).max().unwrap_or(0);
+        let mut pixels = vec![0.0; width * height];
+
+        let mut left = 0;
+        for sprite in sprites {
+            for y in 0..sprite.height {
+                for x in 0..sprite.width {
+                    if sprite.pixel(memory, x, y) {
+                        pixels[y * width + left + x] = 1.0;
+                    }
+                }
+            }
+            left += sprite.width + 1;
+        }
+
+        (pixels, width, height)
+    }
+}
@@ -0,0 +1,144 @@
+use clap::arg_enum;
+use std::time::Duration;
+
+// Attack and release time, long enough to avoid clicks when the buzzer
+// starts or stops mid-cycle
+const ENVELOPE_SECS: f32 = 0.005;
+
+/// Rate of the generated audio files, one 60Hz frame is exactly 735 samples
+pub const SAMPLE_RATE: u32 = 44100;
+
+arg_enum! {
+    #[derive(Clone, Copy, PartialEq)]
+    pub enum Waveform {
+        Square,
+        Sine,
+        Triangle,
+        Noise,
+    }
+}
+
+impl Waveform {
+    /// The next waveform, wrapping around
+    pub fn next(self) -> Self {
+        match self {
+            Waveform::Square => Waveform::Sine,
+            Waveform::Sine => Waveform::Triangle,
+            Waveform::Triangle => Waveform::Noise,
+            Waveform::Noise => Waveform::Square,
+        }
+    }
+}
+
+/// Sound of the buzzer
+#[derive(Clone, Copy, PartialEq)]
+pub struct Tone {
+    pub frequency: f32, // Hz
+    pub waveform: Waveform,
+    pub volume: f32, // 0.0 to 1.0
+    pub muted: bool,
+}
+
+/// Tone generator for the buzzer, shared by audio playback and recording so
+/// both produce the same samples
+pub struct Buzzer {
+    sample_rate: f32,
+    tone: Tone,
+    phase_inc: f32,
+    phase: f32,
+    gain: f32,         // Envelope, 0.0 when silent and 1.0 when sounding
+    gain_step: f32,    // Envelope change per sample
+    noise: u32,        // Xorshift state, deterministic so recordings are too
+    noise_sample: f32, // Held for one period so noise follows the frequency
+}
+
+impl Buzzer {
+    pub fn new(sample_rate: i32, tone: Tone) -> Self {
+        let mut buzzer = Self {
+            sample_rate: sample_rate as f32,
+            tone,
+            phase_inc: 0.0,
+            phase: 0.0,
+            gain: 0.0,
+            gain_step: 1.0 / (ENVELOPE_SECS * sample_rate as f32),
+            noise: 0x1234_5678,
+            noise_sample: 1.0,
+        };
+        buzzer.set_tone(tone);
+        buzzer
+    }
+
+    pub fn set_tone(&mut self, tone: Tone) {
+        self.tone = tone;
+        self.phase_inc = tone.frequency / self.sample_rate;
+    }
+
+    /// Generate samples, ramping the sound in or out depending on whether the
+    /// buzzer is `on`
+    pub fn fill(&mut self, out: &mut [f32], on: bool) {
+        let volume = match self.tone.muted {
+            true => 0.0,
+            false => self.tone.volume,
+        };
+        let target = if on { 1.0 } else { 0.0 };
+        for x in out.iter_mut() {
+            self.gain = match self.gain < target {
+                true => (self.gain + self.gain_step).min(target),
+                false => (self.gain - self.gain_step).max(target),
+            };
+            if self.gain == 0.0 {
+                // Restart the wave from the beginning on the next beep
+                *x = 0.0;
+                self.phase = 0.0;
+                continue;
+            }
+
+            let wave = match self.tone.waveform {
+                Waveform::Square if self.phase < 0.5 => 1.0,
+                Waveform::Square => -1.0,
+                Waveform::Sine => (self.phase * std::f32::consts::TAU).sin(),
+                Waveform::Triangle => 1.0 - 4.0 * (self.phase - 0.5).abs(),
+                Waveform::Noise => self.noise_sample,
+            };
+            *x = volume * self.gain * wave;
+
+            self.phase += self.phase_inc;
+            if self.phase >= 1.0 {
+                self.phase %= 1.0;
+                self.noise ^= self.noise << 13;
+                self.noise ^= self.noise >> 17;
+                self.noise ^= self.noise << 5;
+                self.noise_sample = match self.noise & 1 {
+                    0 => -1.0,
+                    _ => 1.0,
+                };
+            }
+        }
+    }
+}
+
+/// Samples at `sample_rate` in `time`, rounded since nanosecond timestamps
+/// can be just short of a sample
+pub fn sample_count(time: Duration, sample_rate: u32) -> u64 {
+    ((time.as_nanos() * u128::from(sample_rate) + 500_000_000) / 1_000_000_000) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sample_count_rounds_to_nearest() {
+        assert_eq!(sample_count(Duration::from_secs(1), SAMPLE_RATE), 44100);
+        assert_eq!(
+            sample_count(Duration::from_nanos(16_666_666), SAMPLE_RATE),
+            735
+        );
+        assert_eq!(
+            sample_count(Duration::from_nanos(16_666_667), SAMPLE_RATE),
+            735
+        );
+        assert_eq!(sample_count(Duration::from_micros(11), SAMPLE_RATE), 0);
+        assert_eq!(sample_count(Duration::from_micros(12), SAMPLE_RATE), 1);
+    }
+}
@@ -0,0 +1,65 @@
+use crate::synth::{sample_count, Buzzer, Tone, SAMPLE_RATE};
+use std::fs::File;
+use std::io::{self, BufWriter};
+use std::path::Path;
+use std::time::Duration;
+
+/// Writes the buzzer to a 16 bit mono WAV file. Samples are generated from
+/// emulated time, so the output does not depend on emulation speed.
+pub struct WavRecorder {
+    writer: hound::WavWriter<BufWriter<File>>,
+    buzzer: Buzzer,
+    samples: Vec<f32>,
+    written: u64, // Samples written so far
+}
+
+impl WavRecorder {
+    pub fn create(path: &Path, tone: Tone) -> io::Result<Self> {
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate: SAMPLE_RATE,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut recorder = Self {
+            writer: hound::WavWriter::create(path, spec).map_err(io::Error::other)?,
+            buzzer: Buzzer::new(SAMPLE_RATE as i32, tone),
+            samples: Vec::new(),
+            written: 0,
+        };
+        recorder.set_tone(tone);
+        Ok(recorder)
+    }
+
+    /// Change the tone, recordings are never muted
+    pub fn set_tone(&mut self, tone: Tone) {
+        self.buzzer.set_tone(Tone {
+            muted: false,
+            ..tone
+        });
+    }
+
+    /// Write samples up to emulated time `time`, with the buzzer sounding if
+    /// `beep`
+    pub fn record_until(&mut self, time: Duration, beep: bool) -> io::Result<()> {
+        let end = sample_count(time, SAMPLE_RATE);
+        if end <= self.written {
+            return Ok(());
+        }
+
+        self.samples.resize((end - self.written) as usize, 0.0);
+        self.buzzer.fill(&mut self.samples, beep);
+        for sample in &self.samples {
+            let sample = (sample * f32::from(i16::MAX)) as i16;
+            self.writer.write_sample(sample).map_err(io::Error::other)?;
+        }
+        self.written = end;
+
+        Ok(())
+    }
+
+    /// Finalize the WAV header
+    pub fn finish(self) -> io::Result<()> {
+        self.writer.finalize().map_err(io::Error::other)
+    }
+}